@@ -0,0 +1,168 @@
+//! Badges the line numbers are drawn in, styled per operator and line after
+//! the look of their own displays, described in TOML.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Built-in badge tables by name.
+pub const PRESETS: [(&str, &str); 1] = [("swiss", include_str!("../badges/swiss.toml"))];
+
+/// Outline of a badge.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    Rectangle,
+    Rounded,
+    /// A circle, stretched into a pill around long numbers.
+    Circle,
+}
+
+/// Color of a badge.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BadgeColor {
+    #[default]
+    Black,
+    /// The second color of tri-color panels, black on the others.
+    Accent,
+}
+
+/// Style of the badge of a line number.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct Badge {
+    pub shape: Shape,
+    /// Filled with the number in white, rather than outlined.
+    #[serde(default)]
+    pub inverted: bool,
+    #[serde(default)]
+    pub color: BadgeColor,
+}
+
+/// Badges of the lines, the one of a line of an operator taking precedence
+/// over the one of the operator, itself over the default one.
+#[derive(Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct BadgeTable {
+    /// Badge of the other lines, plain text when `None`.
+    pub default: Option<Badge>,
+    /// Badges by operator, as named by the API, e.g. `TPG`.
+    #[serde(default)]
+    pub operators: BTreeMap<String, Badge>,
+    /// Badges by operator and line number, e.g. `TPG` and `12`, numbers
+    /// being reused between operators.
+    #[serde(default)]
+    pub lines: BTreeMap<String, BTreeMap<String, Badge>>,
+}
+
+impl BadgeTable {
+    /// Parses a TOML badge table.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        toml::from_str(s).map_err(|e| format!("{}", e))
+    }
+
+    /// The preset named `name_or_path`, or else the badge table file at this
+    /// path.
+    pub fn load(name_or_path: &str) -> Result<Self, String> {
+        if let Some((_, t)) = PRESETS.iter().find(|(n, _)| *n == name_or_path) {
+            return Ok(BadgeTable::parse(t).expect("presets should be valid"));
+        }
+        let s = std::fs::read_to_string(name_or_path).map_err(|e| {
+            format!(
+                "not a preset among {} nor a readable file: {}",
+                PRESETS.map(|(n, _)| n).join(", "),
+                e
+            )
+        })?;
+        BadgeTable::parse(&s)
+    }
+
+    /// Badge of the line `number` of `operator`, operators being compared
+    /// regardless of their case.
+    pub fn badge(&self, operator: Option<&str>, number: &str) -> Option<Badge> {
+        fn of_operator<'a, T>(table: &'a BTreeMap<String, T>, operator: &str) -> Option<&'a T> {
+            table
+                .iter()
+                .find(|(o, _)| o.eq_ignore_ascii_case(operator))
+                .map(|(_, t)| t)
+        }
+        let by_line = || of_operator(&self.lines, operator?)?.get(number).copied();
+        let by_operator = || of_operator(&self.operators, operator?).copied();
+        by_line().or_else(by_operator).or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_badges() {
+        let table = BadgeTable::parse(
+            r#"
+            default = { shape = "rounded" }
+            [operators.TPG]
+            shape = "rectangle"
+            inverted = true
+            [lines.TPG.12]
+            shape = "circle"
+            color = "accent"
+            [lines.TL.3]
+            shape = "circle"
+            "#,
+        )
+        .unwrap();
+        let badge = |shape, inverted, color| Badge {
+            shape,
+            inverted,
+            color,
+        };
+        assert_eq!(
+            table.badge(Some("TPG"), "12"),
+            Some(badge(Shape::Circle, false, BadgeColor::Accent))
+        );
+        assert_eq!(
+            table.badge(Some("tpg"), "3"),
+            Some(badge(Shape::Rectangle, true, BadgeColor::Black))
+        );
+        // the line 12 of another operator is not the one of TPG
+        assert_eq!(
+            table.badge(Some("TL"), "12"),
+            Some(badge(Shape::Rounded, false, BadgeColor::Black))
+        );
+        assert_eq!(
+            table.badge(Some("TL"), "3"),
+            Some(badge(Shape::Circle, false, BadgeColor::Black))
+        );
+        assert_eq!(
+            table.badge(None, "12"),
+            Some(badge(Shape::Rounded, false, BadgeColor::Black))
+        );
+        assert_eq!(
+            table.badge(None, "3"),
+            Some(badge(Shape::Rounded, false, BadgeColor::Black))
+        );
+        assert_eq!(BadgeTable::default().badge(Some("TPG"), "3"), None);
+    }
+
+    #[test]
+    fn presets() {
+        let swiss = BadgeTable::load("swiss").unwrap();
+        assert!(swiss.badge(Some("TPG"), "3").is_some_and(|b| b.inverted));
+        assert_eq!(swiss.badge(Some("PAG"), "3"), None);
+    }
+
+    #[test]
+    fn invalid_tables() {
+        for t in [
+            "[operators.TPG]\nshape = \"hexagon\"",
+            "[operators.TPG]\ninverted = true",
+            "[operators.TPG]\nshape = \"rounded\"\ncolor = \"red\"",
+            "[colors]\nTPG = \"red\"",
+            "[lines.12]\nshape = \"circle\"",
+        ] {
+            assert!(BadgeTable::parse(t).is_err(), "{}", t);
+        }
+        assert!(BadgeTable::load("tpg").is_err());
+    }
+}
@@ -0,0 +1,616 @@
+//! Fetching the configured board and selecting the lines it displays.
+
+#[cfg(feature = "blocking")]
+use crate::client::paginate;
+#[cfg(feature = "async")]
+use crate::client::paginate_async;
+use crate::config::Config;
+use crate::format::{abbreviate, format_time, short_destination, Alert, Header, LineInfo};
+use crate::icons::journey_icon;
+use crate::model::{BoardType, StationBoardElement, StationBoardResponse};
+use crate::selection::select_fair_share;
+use chrono::{DateTime, Local};
+
+pub const JOURNEYS_LIMIT: u32 = 5;
+// larger set requested when some lines must be guaranteed a slot on the board
+pub const FAIR_SHARE_JOURNEYS_LIMIT: u32 = 40;
+// prefixes arrivals on boards mixing arrivals and departures
+pub const ARRIVAL_MARKER: &str = "←";
+// separates the stations of a merged board in its header
+pub const STATIONS_SEPARATOR: &str = " / ";
+
+/// Whether the entry is of one of the configured transport modes.
+pub fn has_configured_mode(config: &Config, e: &StationBoardElement) -> bool {
+    match &config.transportations {
+        // only keep the requested modes, whatever the API returned
+        Some(t) => e.journey.transport_mode().is_some_and(|m| t.contains(&m)),
+        None => true,
+    }
+}
+
+/// Board merged from the configured stations, along with the stations which
+/// could not be fetched.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MergedBoard {
+    pub board: StationBoardResponse,
+    pub failures: Vec<StationFailure>,
+}
+
+/// A station whose board could not be fetched.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StationFailure {
+    pub station_id: String,
+    pub error: String,
+}
+
+/// Merges the boards of each station by time, the merged board being the one
+/// of the first station fetched. Fails only when no station could be fetched.
+pub fn merge_stations(
+    results: Vec<(String, Result<StationBoardResponse, String>)>,
+) -> Result<MergedBoard, String> {
+    let mut merged: Option<StationBoardResponse> = None;
+    let mut failures = Vec::new();
+    for (station_id, r) in results {
+        match (r, &mut merged) {
+            (Ok(mut b), Some(m)) => m.stationboard.append(&mut b.stationboard),
+            (Ok(b), None) => merged = Some(b),
+            (Err(error), _) => failures.push(StationFailure { station_id, error }),
+        }
+    }
+    for f in &failures {
+        tracing::warn!(
+            station = f.station_id,
+            error = f.error,
+            "station not fetched"
+        );
+    }
+    let Some(mut board) = merged else {
+        let errors: Vec<String> = failures
+            .iter()
+            .map(|f| format!("{}: {}", f.station_id, f.error))
+            .collect();
+        return Err(format!(
+            "no station could be fetched ({})",
+            errors.join("; ")
+        ));
+    };
+    board.stationboard.sort_by_key(|e| e.time());
+    Ok(MergedBoard { board, failures })
+}
+
+/// Fetches the board of each configured station concurrently, giving up on the
+/// ones taking longer than the configured timeout.
+#[cfg(feature = "blocking")]
+pub fn fetch(config: &Config, datetime: Option<DateTime<Local>>) -> Result<MergedBoard, String> {
+    let station_config = std::sync::Arc::new(config.clone());
+    merge_stations(fetch_concurrently(
+        &config.station_ids,
+        config.station_timeout,
+        move |id| fetch_station(&station_config, id, datetime),
+    ))
+}
+
+/// Runs `fetch` on each station in its own thread, the stations not done within
+/// `timeout` being failed.
+#[cfg(feature = "blocking")]
+pub fn fetch_concurrently(
+    station_ids: &[String],
+    timeout: std::time::Duration,
+    fetch: impl Fn(&str) -> Result<StationBoardResponse, String> + Send + Sync + 'static,
+) -> Vec<(String, Result<StationBoardResponse, String>)> {
+    let fetch = std::sync::Arc::new(fetch);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    for (i, id) in station_ids.iter().enumerate() {
+        let (sender, fetch, id) = (sender.clone(), fetch.clone(), id.clone());
+        // a thread still running after the timeout is left to finish on its
+        // own, its result being dropped
+        std::thread::spawn(move || {
+            let _ = sender.send((i, fetch(&id)));
+        });
+    }
+    drop(sender);
+    let deadline = std::time::Instant::now() + timeout;
+    let mut results: Vec<Option<Result<StationBoardResponse, String>>> =
+        station_ids.iter().map(|_| None).collect();
+    while results.iter().any(Option::is_none) {
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        match receiver.recv_timeout(remaining) {
+            Ok((i, r)) => results[i] = Some(r),
+            Err(_) => break,
+        }
+    }
+    station_ids
+        .iter()
+        .cloned()
+        .zip(results)
+        .map(|(id, r)| (id, r.unwrap_or_else(|| Err(timed_out(timeout)))))
+        .collect()
+}
+
+/// Async version of [`fetch`], the stations being fetched concurrently on the
+/// current task.
+#[cfg(feature = "async")]
+pub async fn fetch_async(
+    config: &Config,
+    datetime: Option<DateTime<Local>>,
+) -> Result<MergedBoard, String> {
+    let boards = futures::future::join_all(config.station_ids.iter().map(|id| async move {
+        let r = tokio::time::timeout(
+            config.station_timeout,
+            fetch_station_async(config, id, datetime),
+        )
+        .await
+        .unwrap_or_else(|_| Err(timed_out(config.station_timeout)));
+        (id.clone(), r)
+    }))
+    .await;
+    merge_stations(boards)
+}
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+fn timed_out(timeout: std::time::Duration) -> String {
+    format!("timed out after {}s", timeout.as_secs_f32())
+}
+
+/// Fetches the board of `station_id` at `datetime` (`None` meaning now),
+/// requesting further pages until there are enough entries of the configured
+/// modes to fill the board with each configured line.
+#[cfg(feature = "blocking")]
+pub fn fetch_station(
+    config: &Config,
+    station_id: &str,
+    datetime: Option<DateTime<Local>>,
+) -> Result<StationBoardResponse, String> {
+    let mut station_board = paginate(
+        |d| {
+            StationBoardResponse::get_mixed(
+                station_id,
+                journeys_limit(config),
+                config.transportations.as_deref(),
+                d,
+                &config.board_types,
+            )
+        },
+        datetime,
+        config.horizon,
+        |entries| is_enough(config, entries),
+    )?;
+    retain_configured_modes(config, &mut station_board);
+    Ok(station_board)
+}
+
+/// Removes the entries of modes not configured, the API not filtering all of them.
+#[cfg(any(feature = "blocking", feature = "async"))]
+fn retain_configured_modes(config: &Config, station_board: &mut StationBoardResponse) {
+    let nb_entries = station_board.stationboard.len();
+    station_board
+        .stationboard
+        .retain(|e| has_configured_mode(config, e));
+    tracing::info!(
+        station = station_board.station.id,
+        transportations = ?config.transportations,
+        board_types = ?config.board_types,
+        lines = config.lines.len(),
+        fetched = nb_entries,
+        kept = station_board.stationboard.len(),
+        "filtered board"
+    );
+}
+
+/// Async version of [`fetch_station`].
+#[cfg(feature = "async")]
+pub async fn fetch_station_async(
+    config: &Config,
+    station_id: &str,
+    datetime: Option<DateTime<Local>>,
+) -> Result<StationBoardResponse, String> {
+    let mut station_board = paginate_async(
+        |d| {
+            StationBoardResponse::get_mixed_async(
+                station_id,
+                journeys_limit(config),
+                config.transportations.as_deref(),
+                d,
+                &config.board_types,
+            )
+        },
+        datetime,
+        config.horizon,
+        |entries| is_enough(config, entries),
+    )
+    .await?;
+    retain_configured_modes(config, &mut station_board);
+    Ok(station_board)
+}
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+fn journeys_limit(config: &Config) -> u32 {
+    if config.lines.is_empty() {
+        JOURNEYS_LIMIT
+    } else {
+        FAIR_SHARE_JOURNEYS_LIMIT
+    }
+}
+
+/// Whether `entries` fill the board with each configured line.
+#[cfg(any(feature = "blocking", feature = "async"))]
+fn is_enough(config: &Config, entries: &[StationBoardElement]) -> bool {
+    let kept: Vec<&StationBoardElement> = entries
+        .iter()
+        .filter(|e| has_configured_mode(config, e))
+        .collect();
+    kept.len() >= config.layout.rows
+        && config
+            .lines
+            .iter()
+            .all(|l| kept.iter().any(|e| l.matches(&e.journey)))
+}
+
+/// Whether `e` looks cancelled, the API not flagging cancellations: it has no
+/// realtime prognosis while other entries of its line on `board` have one.
+fn seems_cancelled(e: &StationBoardElement, board: &[StationBoardElement]) -> bool {
+    !e.has_realtime()
+        && board.iter().any(|o| {
+            o.has_realtime()
+                && o.board_type == e.board_type
+                && o.journey.operator == e.journey.operator
+                && o.journey.line_number() == e.journey.line_number()
+        })
+}
+
+/// Display ready lines of the board, at most the rows of the layout.
+pub fn lines_info(station_board: &StationBoardResponse, config: &Config) -> Vec<LineInfo> {
+    let selected = select_fair_share(
+        &station_board.stationboard,
+        &config.lines,
+        config.layout.rows,
+    );
+    let mut lines_info: Vec<LineInfo> = Vec::with_capacity(selected.len());
+    for e in selected {
+        let j = &e.journey;
+        // entries of a merged board come from different stations
+        let station = if e.stop.station.id.is_some() {
+            &e.stop.station
+        } else {
+            &station_board.station
+        };
+        let Some(time) = e.time() else {
+            tracing::warn!(
+                journey = j.name,
+                board_type = %e.board_type,
+                "skipping journey without time"
+            );
+            continue;
+        };
+        let marker = if config.board_types.len() > 1 && e.board_type == BoardType::Arrival {
+            ARRIVAL_MARKER
+        } else {
+            ""
+        };
+        let line_number = j.line_number().unwrap_or_default().to_owned();
+        let badge = config.badges.badge(j.operator.as_deref(), &line_number);
+        let delay = e.delay().unwrap_or_default();
+        let alerts = config
+            .accent
+            .iter()
+            .copied()
+            .filter(|a| match a {
+                Alert::Delay => delay > 0,
+                Alert::Cancellation => seems_cancelled(e, &station_board.stationboard),
+                Alert::PlatformChange => e.stop.platform_changed(),
+            })
+            .collect();
+        lines_info.push(LineInfo {
+            line_number,
+            direction: String::from(marker)
+                + &short_destination(
+                    e.destination_or_origin(station).unwrap_or_default(),
+                    station.name.as_deref().unwrap_or_default(),
+                    &config.abbreviations,
+                    config
+                        .destination_max_chars
+                        .saturating_sub(marker.chars().count()),
+                    |s| s.chars().count(),
+                ),
+            normal_time: format_time(&time, &config.timezone),
+            delay,
+            mode: journey_icon(j, &config.line_icons),
+            badge,
+            alerts,
+        })
+    }
+    lines_info
+}
+
+/// Header of the board updated at `updated`, naming its stations, the ones of
+/// `failures` missing, and telling whether its displayed lines all lack
+/// realtime data.
+pub fn header(
+    station_board: &StationBoardResponse,
+    failures: &[StationFailure],
+    config: &Config,
+    updated: DateTime<Local>,
+) -> Header {
+    let selected = select_fair_share(
+        &station_board.stationboard,
+        &config.lines,
+        config.layout.rows,
+    );
+    let mut stations: Vec<&str> = Vec::new();
+    let names = std::iter::once(&station_board.station)
+        .chain(selected.iter().map(|e| &e.stop.station))
+        .filter_map(|s| s.name.as_deref());
+    for name in names {
+        if !stations.contains(&name) {
+            stations.push(name);
+        }
+    }
+    Header {
+        station: abbreviate(&stations.join(STATIONS_SEPARATOR), &config.abbreviations),
+        updated: format_time(&updated, &config.timezone),
+        stale: false,
+        timetable_only: !selected.is_empty() && !selected.iter().any(|e| e.has_realtime()),
+        failed: failures.iter().map(|f| f.station_id.clone()).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{local_time, stationboard_geneva, stationboard_zurich};
+    use crate::layout::Layout;
+
+    fn config(vars: &[(&str, &str)]) -> Config {
+        Config::from_vars(|name| {
+            vars.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn geneva_lines_info() {
+        let lines: Vec<String> = lines_info(
+            &stationboard_geneva(),
+            &config(&[("STATION_ID", "8587057")]),
+        )
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "3\tGrand-S…\t12:09\t3",
+                "20\tVeyrier…\t12:09\t3",
+                "5\tThônex…\t12:11\t3",
+                "25\tJardin…\t12:11\t5",
+                "19\tOnex…\t12:12\t4",
+            ]
+        );
+    }
+
+    #[test]
+    fn geneva_lines_info_configured_lines() {
+        let lines: Vec<String> = lines_info(
+            &stationboard_geneva(),
+            &config(&[
+                ("STATION_ID", "8587057"),
+                ("LINES", "6:Plage;14"),
+                ("DESTINATION_MAX_CHARS", "30"),
+            ]),
+        )
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "3\tGrand-Saconnex, Giacometti\t12:09\t3",
+                "20\tVeyrier, Tournettes\t12:09\t3",
+                "5\tThônex, Vallard\t12:11\t3",
+                "14\tBernex, Vailly\t12:15\t1",
+                "6\tPlage\t12:17\t1",
+            ]
+        );
+    }
+
+    #[test]
+    fn configured_modes() {
+        let s = stationboard_geneva();
+        let trams = config(&[("STATION_ID", "8587057"), ("TRANSPORTATIONS", "tram")]);
+        let all = config(&[("STATION_ID", "8587057")]);
+        assert!(!has_configured_mode(&trams, &s.stationboard[0]));
+        assert!(has_configured_mode(&trams, &s.stationboard[7]));
+        assert!(has_configured_mode(&all, &s.stationboard[0]));
+    }
+
+    #[test]
+    fn merge_stations_partial_failure() {
+        let merged = merge_stations(vec![
+            (String::from("8503000"), Err(String::from("rate limited"))),
+            (String::from("8587057"), Ok(stationboard_geneva())),
+            (String::from("8503000"), Ok(stationboard_zurich())),
+        ])
+        .unwrap();
+        assert_eq!(merged.board.station, stationboard_geneva().station);
+        assert_eq!(
+            merged.board.stationboard.len(),
+            stationboard_geneva().stationboard.len() + stationboard_zurich().stationboard.len()
+        );
+        assert!(merged
+            .board
+            .stationboard
+            .windows(2)
+            .all(|w| w[0].time() <= w[1].time()));
+        assert_eq!(
+            merged.failures,
+            vec![StationFailure {
+                station_id: String::from("8503000"),
+                error: String::from("rate limited"),
+            }]
+        );
+
+        let e = merge_stations(vec![
+            (String::from("8503000"), Err(String::from("rate limited"))),
+            (String::from("8587057"), Err(String::from("timed out"))),
+        ])
+        .unwrap_err();
+        assert!(e.contains("8503000: rate limited"), "{}", e);
+        assert!(e.contains("8587057: timed out"), "{}", e);
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn fetch_concurrently_times_out() {
+        let ids: Vec<String> = ["slow", "failing", "8587057"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let geneva = stationboard_geneva();
+        // the slow station only returns once the results are in
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let released = std::sync::Mutex::new(released);
+        let timeout = std::time::Duration::from_millis(500);
+        let results = fetch_concurrently(&ids, timeout, move |id| match id {
+            "slow" => {
+                let _ = released.lock().unwrap().recv();
+                Err(String::from("too late"))
+            }
+            "failing" => Err(String::from("rate limited")),
+            _ => Ok(geneva.clone()),
+        });
+        drop(release);
+        let ids_order: Vec<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids_order, vec!["slow", "failing", "8587057"]);
+        assert_eq!(results[0].1, Err(timed_out(timeout)));
+        assert_eq!(results[1].1, Err(String::from("rate limited")));
+        assert_eq!(results[2].1, Ok(stationboard_geneva()));
+    }
+
+    #[test]
+    fn merged_lines_info() {
+        let merged = merge_stations(vec![
+            (String::from("8587057"), Ok(stationboard_geneva())),
+            (String::from("8503000"), Ok(stationboard_zurich())),
+        ])
+        .unwrap();
+        let lines = lines_info(
+            &merged.board,
+            &config(&[
+                ("STATION_ID", "8587057,8503000"),
+                ("DESTINATION_MAX_CHARS", "30"),
+            ]),
+        );
+        // the station of each entry is left out of its destination
+        assert!(lines
+            .iter()
+            .all(|l| !l.direction.starts_with("Zürich") && !l.direction.starts_with("Genève")));
+        assert_eq!(lines.len(), Layout::default().rows);
+    }
+
+    #[test]
+    fn badged_lines_info() {
+        let c = config(&[("STATION_ID", "8587057"), ("BADGES", "swiss")]);
+        let lines = lines_info(&stationboard_geneva(), &c);
+        assert!(lines
+            .iter()
+            .all(|l| l.badge == c.badges.badge(Some("TPG"), &l.line_number) && l.badge.is_some()));
+        // without badges, the line numbers are plain text
+        let lines = lines_info(
+            &stationboard_geneva(),
+            &config(&[("STATION_ID", "8587057")]),
+        );
+        assert!(lines.iter().all(|l| l.badge.is_none()));
+    }
+
+    #[test]
+    fn alerted_lines_info() {
+        let mut board = stationboard_geneva();
+        // the first 3 lost its prognosis, unlike the next one
+        assert!(board.stationboard[1..]
+            .iter()
+            .any(|e| e.journey.number == board.stationboard[0].journey.number));
+        board.stationboard[0].stop.prognosis = None;
+        let stop = &mut board.stationboard[1].stop;
+        stop.platform = Some(String::from("A"));
+        if let Some(p) = stop.prognosis.as_mut() {
+            p.platform = Some(String::from("B"));
+        }
+        let tri_color = config(&[("STATION_ID", "8587057"), ("LAYOUT", "4in2b")]);
+        let lines = lines_info(&board, &tri_color);
+        assert!(lines[0].alerts.contains(&Alert::Cancellation));
+        assert!(lines[1..]
+            .iter()
+            .all(|l| !l.alerts.contains(&Alert::Cancellation)));
+        assert!(lines[1].alerts.contains(&Alert::PlatformChange));
+        // a board without realtime data at all has no cancellations
+        let mut timetable = stationboard_geneva();
+        for e in timetable.stationboard.iter_mut() {
+            e.stop.prognosis = None;
+        }
+        assert!(lines_info(&timetable, &tri_color)
+            .iter()
+            .all(|l| !l.alerts.contains(&Alert::Cancellation)));
+        assert!(lines
+            .iter()
+            .all(|l| l.alerts.contains(&Alert::Delay) == (l.delay > 0)));
+
+        let delays = config(&[
+            ("STATION_ID", "8587057"),
+            ("LAYOUT", "4in2b"),
+            ("ACCENT", "delays"),
+        ]);
+        assert!(lines_info(&board, &delays)
+            .iter()
+            .all(|l| l.alerts.iter().all(|a| *a == Alert::Delay)));
+        // black and white panels have no accent
+        let lines = lines_info(&board, &config(&[("STATION_ID", "8587057")]));
+        assert!(lines.iter().all(|l| l.alerts.is_empty()));
+    }
+
+    #[test]
+    fn board_header() {
+        let geneva = config(&[("STATION_ID", "8587057")]);
+        let updated = local_time("2024-04-19T12:05:00+0200");
+        assert_eq!(
+            header(&stationboard_geneva(), &[], &geneva, updated),
+            Header {
+                station: String::from("Genève, g. Cornavin"),
+                updated: String::from("12:05"),
+                stale: false,
+                timetable_only: false,
+                failed: Vec::new(),
+            }
+        );
+
+        let mut board = stationboard_geneva();
+        for e in &mut board.stationboard {
+            e.stop.prognosis = None;
+        }
+        assert!(header(&board, &[], &geneva, updated).timetable_only);
+        board.stationboard.clear();
+        assert!(!header(&board, &[], &geneva, updated).timetable_only);
+
+        let merged = merge_stations(vec![
+            (String::from("8587057"), Ok(stationboard_geneva())),
+            (String::from("8503000"), Ok(stationboard_zurich())),
+        ])
+        .unwrap();
+        // the RE 37 leaving Zürich HB is given a row
+        let merged_config = config(&[("STATION_ID", "8587057,8503000"), ("LINES", "37")]);
+        assert_eq!(
+            header(&merged.board, &merged.failures, &merged_config, updated).station,
+            "Genève, g. Cornavin / Zürich HB"
+        );
+
+        let merged = merge_stations(vec![
+            (String::from("8587057"), Ok(stationboard_geneva())),
+            (String::from("8503000"), Err(String::from("rate limited"))),
+        ])
+        .unwrap();
+        let partial = header(&merged.board, &merged.failures, &merged_config, updated);
+        assert_eq!(partial.station, "Genève, g. Cornavin");
+        assert_eq!(partial.failed, vec![String::from("8503000")]);
+    }
+}
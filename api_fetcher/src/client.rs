@@ -0,0 +1,543 @@
+//! Client of the stationboard endpoint, blocking with the `blocking` feature
+//! (default) and async with the `async` one.
+
+use crate::model::{BoardType, StationBoardElement, StationBoardResponse, TransportMode};
+use chrono::{DateTime, Local};
+use chrono_tz::Tz;
+use reqwest::Url;
+use std::collections::HashSet;
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(any(feature = "blocking", feature = "async"))]
+use std::time::Instant;
+
+pub const STATIONBOARD_ENDPOINT: &str = "https://transport.opendata.ch/v1/stationboard";
+// the API interprets the requested datetime as swiss local time
+pub const API_TIMEZONE: Tz = chrono_tz::Europe::Zurich;
+// bounds the number of requests of a single run
+pub const MAX_PAGES: usize = 10;
+// request budget left, as sent back by the API
+pub const RATE_LIMIT_REMAINING_HEADER: &str = "x-ratelimit-remaining";
+
+/// Url requesting the board of a `station` name or `id`, every other parameter
+/// being optional.
+// written before the crate was linted
+#[allow(clippy::partialeq_to_none, clippy::single_match)]
+pub fn stationboard_url(
+    station: Option<&str>,
+    id: Option<&str>,
+    limit: Option<u32>,
+    transportations: Option<&[TransportMode]>,
+    datetime: Option<DateTime<Local>>,
+    r#type: Option<BoardType>,
+) -> Result<Url, String> {
+    if station == None && id == None {
+        return Err(String::from("must provide either a station or an id"));
+    }
+    let mut args: Vec<(&str, String)> = Vec::with_capacity(6);
+    match station {
+        Some(s) => args.push(("station", s.to_owned())),
+        _ => (),
+    }
+    match id {
+        Some(s) => args.push(("id", s.to_owned())),
+        _ => (),
+    }
+    match r#type {
+        Some(t) => args.push(("type", t.api_value().to_owned())),
+        _ => (),
+    }
+    match limit {
+        Some(l) => args.push(("limit", l.to_string())),
+        _ => (),
+    }
+    match transportations {
+        Some(t) => {
+            for e in t {
+                args.push(("transportations", e.api_value().to_owned()))
+            }
+        }
+        _ => (),
+    }
+    match datetime {
+        Some(d) => args.push((
+            "datetime",
+            format!(
+                "{}",
+                d.with_timezone(&API_TIMEZONE).format("%Y-%m-%d %H:%M")
+            ),
+        )),
+        _ => (),
+    }
+
+    Url::parse_with_params(STATIONBOARD_ENDPOINT, args)
+        .or(Err(String::from("url parameters should be parsable")))
+}
+
+impl StationBoardResponse {
+    /// Requests the board of a `station` name or `id`, every other parameter
+    /// being optional.
+    #[cfg(feature = "blocking")]
+    pub fn get(
+        station: Option<&str>,
+        id: Option<&str>,
+        limit: Option<u32>,
+        transportations: Option<&[TransportMode]>,
+        datetime: Option<DateTime<Local>>,
+        r#type: Option<BoardType>,
+    ) -> Result<Self, String> {
+        let url = stationboard_url(station, id, limit, transportations, datetime, r#type)?;
+        let start = Instant::now();
+        let response = reqwest::blocking::get(url.clone())
+            .map_err(|e| format!("could not perform get request: {}", e))?;
+        let status = response.status();
+        let rate_limit_remaining = rate_limit_remaining(response.headers());
+        let response = response
+            .json::<StationBoardResponse>()
+            .map_err(|e| format!("could not parse json received: {}", e))?;
+        record_request(&url, status, rate_limit_remaining, start, &response);
+        Ok(response.with_board_type(r#type.unwrap_or_default()))
+    }
+
+    /// Async version of [`get`](Self::get), to be run on a tokio runtime.
+    #[cfg(feature = "async")]
+    pub async fn get_async(
+        station: Option<&str>,
+        id: Option<&str>,
+        limit: Option<u32>,
+        transportations: Option<&[TransportMode]>,
+        datetime: Option<DateTime<Local>>,
+        r#type: Option<BoardType>,
+    ) -> Result<Self, String> {
+        let url = stationboard_url(station, id, limit, transportations, datetime, r#type)?;
+        let start = Instant::now();
+        let response = reqwest::get(url.clone())
+            .await
+            .map_err(|e| format!("could not perform get request: {}", e))?;
+        let status = response.status();
+        let rate_limit_remaining = rate_limit_remaining(response.headers());
+        let response = response
+            .json::<StationBoardResponse>()
+            .await
+            .map_err(|e| format!("could not parse json received: {}", e))?;
+        record_request(&url, status, rate_limit_remaining, start, &response);
+        Ok(response.with_board_type(r#type.unwrap_or_default()))
+    }
+
+    /// Fetches a board for each of `board_types` and merges them by time.
+    #[cfg(feature = "blocking")]
+    pub fn get_mixed(
+        id: &str,
+        limit: u32,
+        transportations: Option<&[TransportMode]>,
+        datetime: Option<DateTime<Local>>,
+        board_types: &[BoardType],
+    ) -> Result<Self, String> {
+        let boards = board_types.iter().map(|t| {
+            Self::get(
+                None,
+                Some(id),
+                Some(limit),
+                transportations,
+                datetime,
+                Some(*t),
+            )
+        });
+        merge_boards(boards)
+    }
+
+    /// Async version of [`get_mixed`](Self::get_mixed), the board types being
+    /// requested concurrently.
+    #[cfg(feature = "async")]
+    pub async fn get_mixed_async(
+        id: &str,
+        limit: u32,
+        transportations: Option<&[TransportMode]>,
+        datetime: Option<DateTime<Local>>,
+        board_types: &[BoardType],
+    ) -> Result<Self, String> {
+        let boards = futures::future::join_all(board_types.iter().map(|t| {
+            Self::get_async(
+                None,
+                Some(id),
+                Some(limit),
+                transportations,
+                datetime,
+                Some(*t),
+            )
+        }))
+        .await;
+        merge_boards(boards)
+    }
+
+    #[cfg(any(feature = "blocking", feature = "async"))]
+    fn with_board_type(mut self, board_type: BoardType) -> Self {
+        for e in self.stationboard.iter_mut() {
+            e.board_type = board_type;
+        }
+        self
+    }
+}
+
+/// Logs a request, also recording it in the metrics with the `metrics` feature.
+#[cfg(any(feature = "blocking", feature = "async"))]
+fn record_request(
+    url: &Url,
+    status: reqwest::StatusCode,
+    rate_limit_remaining: Option<i64>,
+    start: Instant,
+    response: &StationBoardResponse,
+) {
+    let latency = start.elapsed();
+    tracing::info!(
+        %url,
+        %status,
+        latency_ms = latency.as_millis() as u64,
+        departures = response.stationboard.len(),
+        rate_limit_remaining,
+        "stationboard request"
+    );
+    #[cfg(feature = "metrics")]
+    crate::metrics::observe_request(latency, rate_limit_remaining);
+}
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+fn rate_limit_remaining(headers: &reqwest::header::HeaderMap) -> Option<i64> {
+    headers
+        .get(RATE_LIMIT_REMAINING_HEADER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(any(feature = "blocking", feature = "async"))]
+fn merge_boards(
+    boards: impl IntoIterator<Item = Result<StationBoardResponse, String>>,
+) -> Result<StationBoardResponse, String> {
+    let mut boards = boards.into_iter();
+    let mut merged = boards
+        .next()
+        .ok_or(String::from("must request at least one board type"))??;
+    for b in boards {
+        merged.stationboard.append(&mut b?.stationboard);
+    }
+    merged.stationboard.sort_by_key(|e| e.time());
+    Ok(merged)
+}
+
+/// Fetches the board at `datetime` (`None` meaning now), then the following
+/// pages each starting at the last time seen, until `enough` holds on the
+/// entries gathered so far, the last time seen is past `horizon`, a page brings
+/// nothing new, or `MAX_PAGES` pages were fetched. Entries seen on several pages
+/// are kept once.
+pub fn paginate(
+    mut fetch: impl FnMut(Option<DateTime<Local>>) -> Result<StationBoardResponse, String>,
+    datetime: Option<DateTime<Local>>,
+    horizon: chrono::Duration,
+    enough: impl Fn(&[StationBoardElement]) -> bool,
+) -> Result<StationBoardResponse, String> {
+    let mut pages = Pages::new(fetch(datetime)?, datetime, horizon);
+    for _ in 1..MAX_PAGES {
+        let Some(next) = pages.next_datetime(&enough) else {
+            break;
+        };
+        if !pages.add(fetch(Some(next))?) {
+            break;
+        }
+    }
+    Ok(pages.into_board())
+}
+
+/// Async version of [`paginate`].
+#[cfg(feature = "async")]
+pub async fn paginate_async<F: Future<Output = Result<StationBoardResponse, String>>>(
+    mut fetch: impl FnMut(Option<DateTime<Local>>) -> F,
+    datetime: Option<DateTime<Local>>,
+    horizon: chrono::Duration,
+    enough: impl Fn(&[StationBoardElement]) -> bool,
+) -> Result<StationBoardResponse, String> {
+    let mut pages = Pages::new(fetch(datetime).await?, datetime, horizon);
+    for _ in 1..MAX_PAGES {
+        let Some(next) = pages.next_datetime(&enough) else {
+            break;
+        };
+        if !pages.add(fetch(Some(next)).await?) {
+            break;
+        }
+    }
+    Ok(pages.into_board())
+}
+
+/// Board gathered over the pages fetched so far.
+struct Pages {
+    board: StationBoardResponse,
+    seen: HashSet<PageKey>,
+    end: DateTime<Local>,
+}
+
+impl Pages {
+    fn new(
+        board: StationBoardResponse,
+        datetime: Option<DateTime<Local>>,
+        horizon: chrono::Duration,
+    ) -> Self {
+        Pages {
+            seen: board.stationboard.iter().map(page_key).collect(),
+            board,
+            end: datetime.unwrap_or_else(Local::now) + horizon,
+        }
+    }
+
+    /// Datetime the next page starts at, `None` when no more page is needed.
+    fn next_datetime(
+        &self,
+        enough: impl Fn(&[StationBoardElement]) -> bool,
+    ) -> Option<DateTime<Local>> {
+        let last = self
+            .board
+            .stationboard
+            .iter()
+            .filter_map(|e| e.time())
+            .max()?;
+        if enough(&self.board.stationboard) || last > self.end {
+            return None;
+        }
+        Some(last)
+    }
+
+    /// Adds the entries of `page` not seen yet, returning whether there were any.
+    fn add(&mut self, page: StationBoardResponse) -> bool {
+        let nb_entries = self.board.stationboard.len();
+        for e in page.stationboard {
+            if self.seen.insert(page_key(&e)) {
+                self.board.stationboard.push(e);
+            }
+        }
+        self.board.stationboard.len() > nb_entries
+    }
+
+    fn into_board(mut self) -> StationBoardResponse {
+        self.board.stationboard.sort_by_key(|e| e.time());
+        self.board
+    }
+}
+
+/// Identifies an entry across pages: a journey at a given time and station.
+type PageKey = (
+    BoardType,
+    Option<DateTime<Local>>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+fn page_key(e: &StationBoardElement) -> PageKey {
+    (
+        e.board_type,
+        e.time(),
+        e.journey.name.clone(),
+        e.journey.number.clone(),
+        e.journey.to.clone(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{local_time, stationboard_geneva};
+
+    /// Serves pages of the Geneva board as the API would, `limit` entries from
+    /// the requested minute on, counting the requests in `nb_requests`.
+    fn geneva_pages(
+        limit: usize,
+        nb_requests: &std::cell::Cell<usize>,
+    ) -> impl FnMut(Option<DateTime<Local>>) -> Result<StationBoardResponse, String> + '_ {
+        let board = stationboard_geneva();
+        move |d| {
+            nb_requests.set(nb_requests.get() + 1);
+            let d = d.unwrap_or(local_time("2024-04-19T12:09:00+0200"));
+            Ok(StationBoardResponse {
+                station: board.station.clone(),
+                stationboard: board
+                    .stationboard
+                    .iter()
+                    .filter(|e| e.time().unwrap() >= d)
+                    .take(limit)
+                    .cloned()
+                    .collect(),
+            })
+        }
+    }
+
+    #[test]
+    fn paginate_until_enough() {
+        let nb_requests = std::cell::Cell::new(0);
+        let horizon = chrono::Duration::minutes(60);
+        let start = Some(local_time("2024-04-19T12:09:00+0200"));
+        let has_line_18 = |entries: &[StationBoardElement]| {
+            entries
+                .iter()
+                .any(|e| e.journey.number == Some(String::from("18")))
+        };
+        let b = paginate(geneva_pages(5, &nb_requests), start, horizon, has_line_18).unwrap();
+        // line 18 first leaves at 12:18, as the 20th entry
+        assert_eq!(nb_requests.get(), 6);
+        assert!(has_line_18(&b.stationboard));
+        assert!(b.stationboard.len() >= 20);
+        assert_eq!(
+            b.stationboard,
+            stationboard_geneva().stationboard[..b.stationboard.len()]
+        );
+
+        nb_requests.set(0);
+        let b = paginate(geneva_pages(5, &nb_requests), start, horizon, |_| true).unwrap();
+        assert_eq!(nb_requests.get(), 1);
+        assert_eq!(b.stationboard.len(), 5);
+    }
+
+    #[test]
+    fn paginate_stops_at_horizon() {
+        let nb_requests = std::cell::Cell::new(0);
+        let start = Some(local_time("2024-04-19T12:09:00+0200"));
+        let b = paginate(
+            geneva_pages(5, &nb_requests),
+            start,
+            chrono::Duration::minutes(4),
+            |_| false,
+        )
+        .unwrap();
+        // the first page goes up to 12:12, the second one to 12:14
+        assert_eq!(nb_requests.get(), 2);
+        assert_eq!(b.stationboard.len(), 9);
+    }
+
+    #[test]
+    fn paginate_stops_without_progress() {
+        let nb_requests = std::cell::Cell::new(0);
+        let b = paginate(
+            geneva_pages(50, &nb_requests),
+            None,
+            chrono::Duration::days(1),
+            |_| false,
+        )
+        .unwrap();
+        assert_eq!(nb_requests.get(), 2);
+        assert_eq!(b.stationboard, stationboard_geneva().stationboard);
+
+        // more entries in a minute than the limit
+        nb_requests.set(0);
+        let b = paginate(
+            geneva_pages(2, &nb_requests),
+            None,
+            chrono::Duration::days(1),
+            |_| false,
+        )
+        .unwrap();
+        assert_eq!(nb_requests.get(), 2);
+        assert_eq!(b.stationboard.len(), 2);
+    }
+
+    #[test]
+    fn paginate_errors() {
+        let mut nb_requests = 0;
+        let r = paginate(
+            |_| {
+                nb_requests += 1;
+                if nb_requests == 1 {
+                    Ok(stationboard_geneva())
+                } else {
+                    Err(String::from("rate limited"))
+                }
+            },
+            None,
+            chrono::Duration::days(1),
+            |_| false,
+        );
+        assert_eq!(r, Err(String::from("rate limited")));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn paginate_async_as_blocking() {
+        let start = Some(local_time("2024-04-19T12:09:00+0200"));
+        let horizon = chrono::Duration::minutes(60);
+        let enough = |entries: &[StationBoardElement]| entries.len() >= 12;
+        let nb_requests = std::cell::Cell::new(0);
+        let b = paginate(geneva_pages(5, &nb_requests), start, horizon, enough).unwrap();
+
+        let nb_async_requests = std::cell::Cell::new(0);
+        let mut fetch = geneva_pages(5, &nb_async_requests);
+        let b_async = paginate_async(|d| std::future::ready(fetch(d)), start, horizon, enough)
+            .await
+            .unwrap();
+        assert_eq!(b_async, b);
+        assert_eq!(nb_async_requests.get(), nb_requests.get());
+    }
+
+    #[test]
+    fn url_params() {
+        let url = stationboard_url(
+            None,
+            Some("8587057"),
+            Some(3),
+            Some(&[TransportMode::Tram, TransportMode::Bus]),
+            Some(local_time("2024-04-19T10:09:00+0000")),
+            Some(BoardType::Arrival),
+        )
+        .unwrap();
+        assert_eq!(
+            url.as_str(),
+            format!(
+                "{}?id=8587057&type=arrival&limit=3&transportations=tram\
+                 &transportations=bus&datetime=2024-04-19+12%3A09",
+                STATIONBOARD_ENDPOINT
+            )
+        );
+        assert!(stationboard_url(None, None, None, None, None, None).is_err());
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn test_api_call_all_params() {
+        StationBoardResponse::get(
+            Some("Genève, Cornavin"),
+            Some("8587057"),
+            Some(3),
+            Some(&[TransportMode::Metro, TransportMode::Tram]),
+            Some(chrono::Local::now()),
+            Some(BoardType::Arrival),
+        )
+        .expect("error with the API call");
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    #[allow(clippy::expect_fun_call)]
+    fn test_api_different_stations() {
+        for s in [
+            "Genève, gare Cornavin",
+            "Zürich HB",
+            "Lausanne, gare",
+            "Bern, Bahnhof",
+        ] {
+            StationBoardResponse::get(Some(s), None, None, None, None, None)
+                .expect(&format!("error with the API call for station {}", s));
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_api_call_async() {
+        StationBoardResponse::get_mixed_async(
+            "8587057",
+            3,
+            None,
+            None,
+            &[BoardType::Departure, BoardType::Arrival],
+        )
+        .await
+        .expect("error with the API call");
+    }
+}
@@ -0,0 +1,728 @@
+//! Configuration read from the environment, usually filled from a `.env` file.
+
+use crate::badges::BadgeTable;
+use crate::datetime::{parse_board_datetime, BoardDateTime};
+use crate::format::{parse_abbreviations, parse_alerts, Alert};
+use crate::icons::parse_line_icons;
+use crate::layout::{Layout, DEFAULT_LAYOUT};
+use crate::model::{parse_board_types, parse_transport_modes, BoardType, TransportMode};
+use crate::render::Style;
+use crate::render::{DEFAULT_FONT_FILE, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use crate::selection::{parse_line_selectors, LineSelector};
+use chrono::NaiveTime;
+use chrono_tz::Tz;
+
+pub const DEFAULT_TIMEZONE: Tz = chrono_tz::Europe::Zurich;
+pub const DEFAULT_ABBREVIATIONS: &str =
+    "Bahnhof=Bhf;Hauptbahnhof=HB;Flughafen=Flugh.;gare=g.;Gare=G.;Aéroport=Aérop.";
+// the Python display cuts longer destinations to their first and last 3
+// characters, making it the effective limit unless the board is drawn here
+pub const DESTINATION_MAX_CHARS: usize = 8;
+// how far ahead of the board datetime further pages are requested
+pub const DEFAULT_HORIZON_MINUTES: i64 = 120;
+// after which a station still being fetched is given up
+pub const DEFAULT_STATION_TIMEOUT_SECONDS: u64 = 20;
+// only problems are logged by default, keeping cron runs quiet
+pub const DEFAULT_LOG_LEVEL: &str = "warn";
+pub const DEFAULT_HEALTH_FILE: &str = "health.json";
+pub const DEFAULT_DISPLAYED_FILE: &str = "displayed.json";
+// an unchanged board is displayed again after this long, in case a display failed
+pub const DEFAULT_FORCED_DISPLAY_MINUTES: i64 = 60;
+// a few missed updates are tolerated before the board is reported unhealthy
+pub const DEFAULT_HEALTH_MAX_AGE_MINUTES: i64 = 15;
+pub const DEFAULT_EPD_SIMULATOR_FILE: &str = "epd_simulator.png";
+pub const DEFAULT_EPD_STATE_FILE: &str = "epd_state.json";
+// partial refreshes between full ones, each leaving a bit more ghosting
+pub const DEFAULT_FULL_REFRESH_EVERY: u32 = 10;
+
+/// Output format of the logs.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum LogFormat {
+    /// Human readable, colored on terminals.
+    #[default]
+    Pretty,
+    /// One JSON object per event.
+    Json,
+    /// Without timestamps nor colors, which journald adds or does not render.
+    Journald,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            "journald" => Ok(LogFormat::Journald),
+            other => Err(format!(
+                "unknown log format \"{}\", expected pretty, json or journald",
+                other
+            )),
+        }
+    }
+}
+
+/// Backend the e-paper driver pushes the board to.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EpdBackend {
+    /// The panel of the e-paper HAT, through Linux spidev and gpio-cdev.
+    Linux,
+    /// A simulated panel, whose displayed image is saved.
+    Simulator,
+}
+
+impl std::str::FromStr for EpdBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "linux" => Ok(EpdBackend::Linux),
+            "simulator" => Ok(EpdBackend::Simulator),
+            other => Err(format!(
+                "unknown e-paper backend \"{}\", expected linux or simulator",
+                other
+            )),
+        }
+    }
+}
+
+/// Configuration of the board, each field documenting its variable.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Config {
+    /// `STATION_ID`, the only mandatory variable, "," separated to merge the
+    /// boards of several stations.
+    pub station_ids: Vec<String>,
+    /// `LINES`, see [`parse_line_selectors`].
+    pub lines: Vec<LineSelector>,
+    /// `ABBREVIATIONS`, see [`parse_abbreviations`].
+    pub abbreviations: Vec<(String, String)>,
+    /// `DESTINATION_MAX_CHARS`.
+    pub destination_max_chars: usize,
+    /// `TIMEZONE` of the displayed times, as a tz database name.
+    pub timezone: Tz,
+    /// `TRANSPORTATIONS`, all modes when `None`.
+    pub transportations: Option<Vec<TransportMode>>,
+    /// `BOARD_TYPE`, see [`parse_board_types`].
+    pub board_types: Vec<BoardType>,
+    /// `BOARD_DATETIME`, now when `None`.
+    pub board_datetime: Option<BoardDateTime>,
+    /// `MORNING_PREVIEW_FROM` and `MORNING_PREVIEW_AT`.
+    pub morning_preview: Option<(NaiveTime, NaiveTime)>,
+    /// `HORIZON_MINUTES`.
+    pub horizon: chrono::Duration,
+    /// `STATION_TIMEOUT_SECONDS`.
+    pub station_timeout: std::time::Duration,
+    /// `LOG_LEVEL`, falling back on `RUST_LOG`, as tracing filter directives,
+    /// e.g. "info" or "warn,api_fetcher=debug".
+    pub log_level: String,
+    /// `LOG_FORMAT`.
+    pub log_format: LogFormat,
+    /// `UPDATE_INTERVAL_SECONDS`, updating the board in a loop rather than once
+    /// when set.
+    pub update_interval: Option<std::time::Duration>,
+    /// `METRICS_ADDRESS` the metrics are served on, requiring the `metrics`
+    /// feature.
+    pub metrics_address: Option<std::net::SocketAddr>,
+    /// `HEALTH_FILE` written after each update.
+    pub health_file: std::path::PathBuf,
+    /// `HEALTH_MAX_AGE_MINUTES` after the last successful update.
+    pub health_max_age: chrono::Duration,
+    /// `DISPLAYED_FILE` keeping the board last displayed, see
+    /// [`Displayed`](crate::displayed::Displayed).
+    pub displayed_file: std::path::PathBuf,
+    /// `FORCED_DISPLAY_MINUTES` after which an unchanged board is displayed
+    /// again, 0 displaying it at each update.
+    pub forced_display_after: chrono::Duration,
+    /// `IMAGE_FILE` the board is drawn to, as bmp or png, when set.
+    pub image_file: Option<std::path::PathBuf>,
+    /// `FONT_FILE` of the drawn board, the first font of collections.
+    pub font_file: std::path::PathBuf,
+    /// `LAYOUT` preset name or file, see [`Layout::load`], its number of
+    /// `ROWS` and `HEADER_HEIGHT` being overridden, as well as its style by
+    /// `FONT_SIZE`, `ROW_HEIGHT`, `SEPARATOR_OFFSET`, `SEPARATOR_THICKNESS`,
+    /// `LINE_COLUMN_WIDTH`, `DELAY_COLUMN_WIDTH`, `COLUMN_GAP`,
+    /// `HEADER_FONT_SIZE` and `ICON_SIZE`, in pixels.
+    pub layout: Layout,
+    /// `LINE_ICONS` overriding the mode of the icon of some lines, see
+    /// [`parse_line_icons`].
+    pub line_icons: Vec<(LineSelector, TransportMode)>,
+    /// `BADGES` preset name or file, see [`BadgeTable::load`], the line
+    /// numbers being plain text when unset.
+    pub badges: BadgeTable,
+    /// `ACCENT` alerts of the lines drawn in the accent color, see
+    /// [`parse_alerts`], all of them by default on tri-color layouts and none
+    /// on the others.
+    pub accent: Vec<Alert>,
+    /// `EPD` backend the board is pushed to after each update, requiring the
+    /// `epd` feature, and `epd-linux` for [`EpdBackend::Linux`].
+    pub epd: Option<EpdBackend>,
+    /// `EPD_SIMULATOR_FILE` the image of the simulated panel is saved to.
+    pub epd_simulator_file: std::path::PathBuf,
+    /// `EPD_STATE_FILE` the refreshes of the panel are kept in, the displayed
+    /// frame being saved next to it.
+    pub epd_state_file: std::path::PathBuf,
+    /// `FULL_REFRESH_EVERY` updates of the panel, the others being partial.
+    pub full_refresh_every: u32,
+    /// `FULL_REFRESH_AT`, "," separated times in `TIMEZONE` after which the next
+    /// update of the panel is a full refresh.
+    pub full_refresh_at: Vec<NaiveTime>,
+}
+
+impl Config {
+    /// Reads the configuration from the environment variables.
+    pub fn from_env() -> Result<Self, String> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Reads the configuration from `var`, giving the value of a variable from
+    /// its name. Empty variables are considered unset.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let var = |name: &str| var(name).filter(|v| !v.trim().is_empty());
+        let invalid = |name: &str, e: String| format!("invalid {}: {}", name, e);
+
+        let station_ids: Vec<String> = var("STATION_ID")
+            .ok_or(String::from("STATION_ID must be set"))?
+            .split(',')
+            .map(|id| id.trim().to_owned())
+            .collect();
+        if station_ids.iter().any(|id| id.is_empty()) {
+            return Err(invalid("STATION_ID", String::from("empty station id")));
+        }
+        let lines =
+            parse_var_with(var("LINES"), "LINES", parse_line_selectors)?.unwrap_or_default();
+        let abbreviations = parse_abbreviations(
+            &var("ABBREVIATIONS").unwrap_or(String::from(DEFAULT_ABBREVIATIONS)),
+        )
+        .map_err(|e| invalid("ABBREVIATIONS", e))?;
+        let destination_max_chars = parse_var(
+            var("DESTINATION_MAX_CHARS"),
+            "DESTINATION_MAX_CHARS",
+            DESTINATION_MAX_CHARS,
+        )?;
+        let timezone = parse_var(var("TIMEZONE"), "TIMEZONE", DEFAULT_TIMEZONE)?;
+        let transportations = parse_var_with(
+            var("TRANSPORTATIONS"),
+            "TRANSPORTATIONS",
+            parse_transport_modes,
+        )?;
+        let board_types = parse_var_with(var("BOARD_TYPE"), "BOARD_TYPE", parse_board_types)?
+            .unwrap_or(vec![BoardType::Departure]);
+        let board_datetime = parse_var_with(
+            var("BOARD_DATETIME"),
+            "BOARD_DATETIME",
+            parse_board_datetime,
+        )?;
+        let morning_preview = match (
+            parse_var_with(
+                var("MORNING_PREVIEW_FROM"),
+                "MORNING_PREVIEW_FROM",
+                parse_hours_minutes,
+            )?,
+            parse_var_with(
+                var("MORNING_PREVIEW_AT"),
+                "MORNING_PREVIEW_AT",
+                parse_hours_minutes,
+            )?,
+        ) {
+            (Some(from), Some(at)) => Some((from, at)),
+            _ => None,
+        };
+        let horizon = chrono::Duration::minutes(parse_var(
+            var("HORIZON_MINUTES"),
+            "HORIZON_MINUTES",
+            DEFAULT_HORIZON_MINUTES,
+        )?);
+        let station_timeout = std::time::Duration::from_secs(parse_var(
+            var("STATION_TIMEOUT_SECONDS"),
+            "STATION_TIMEOUT_SECONDS",
+            DEFAULT_STATION_TIMEOUT_SECONDS,
+        )?);
+        let log_level = var("LOG_LEVEL")
+            .or_else(|| var("RUST_LOG"))
+            .unwrap_or(String::from(DEFAULT_LOG_LEVEL));
+        let log_format = parse_var(var("LOG_FORMAT"), "LOG_FORMAT", LogFormat::default())?;
+        let update_interval =
+            parse_optional_var(var("UPDATE_INTERVAL_SECONDS"), "UPDATE_INTERVAL_SECONDS")?
+                .map(std::time::Duration::from_secs);
+        let metrics_address = parse_optional_var(var("METRICS_ADDRESS"), "METRICS_ADDRESS")?;
+        let health_file = var("HEALTH_FILE")
+            .unwrap_or(String::from(DEFAULT_HEALTH_FILE))
+            .into();
+        let health_max_age = chrono::Duration::minutes(parse_var(
+            var("HEALTH_MAX_AGE_MINUTES"),
+            "HEALTH_MAX_AGE_MINUTES",
+            DEFAULT_HEALTH_MAX_AGE_MINUTES,
+        )?);
+        let displayed_file = var("DISPLAYED_FILE")
+            .unwrap_or(String::from(DEFAULT_DISPLAYED_FILE))
+            .into();
+        let forced_display_after = chrono::Duration::minutes(parse_var(
+            var("FORCED_DISPLAY_MINUTES"),
+            "FORCED_DISPLAY_MINUTES",
+            DEFAULT_FORCED_DISPLAY_MINUTES,
+        )?);
+        let image_file = var("IMAGE_FILE").map(|f| f.into());
+        let font_file = var("FONT_FILE")
+            .unwrap_or(String::from(DEFAULT_FONT_FILE))
+            .into();
+        let mut layout = Layout::load(&var("LAYOUT").unwrap_or(String::from(DEFAULT_LAYOUT)))
+            .map_err(|e| invalid("LAYOUT", e))?;
+        layout.rows = parse_var(var("ROWS"), "ROWS", layout.rows)?;
+        layout.header_height =
+            parse_var(var("HEADER_HEIGHT"), "HEADER_HEIGHT", layout.header_height)?;
+        let default_style = layout.style;
+        layout.style = Style {
+            font_size: parse_var(var("FONT_SIZE"), "FONT_SIZE", default_style.font_size)?,
+            row_height: parse_var(var("ROW_HEIGHT"), "ROW_HEIGHT", default_style.row_height)?,
+            separator_offset: parse_var(
+                var("SEPARATOR_OFFSET"),
+                "SEPARATOR_OFFSET",
+                default_style.separator_offset,
+            )?,
+            separator_thickness: parse_var(
+                var("SEPARATOR_THICKNESS"),
+                "SEPARATOR_THICKNESS",
+                default_style.separator_thickness,
+            )?,
+            line_width: parse_var(
+                var("LINE_COLUMN_WIDTH"),
+                "LINE_COLUMN_WIDTH",
+                default_style.line_width,
+            )?,
+            delay_width: parse_var(
+                var("DELAY_COLUMN_WIDTH"),
+                "DELAY_COLUMN_WIDTH",
+                default_style.delay_width,
+            )?,
+            column_gap: parse_var(var("COLUMN_GAP"), "COLUMN_GAP", default_style.column_gap)?,
+            header_font_size: parse_var(
+                var("HEADER_FONT_SIZE"),
+                "HEADER_FONT_SIZE",
+                default_style.header_font_size,
+            )?,
+            icon_size: parse_var(var("ICON_SIZE"), "ICON_SIZE", default_style.icon_size)?,
+            ..default_style
+        };
+        layout.check().map_err(|e| invalid("LAYOUT", e))?;
+        let line_icons =
+            parse_var_with(var("LINE_ICONS"), "LINE_ICONS", parse_line_icons)?.unwrap_or_default();
+        let badges = parse_var_with(var("BADGES"), "BADGES", BadgeTable::load)?.unwrap_or_default();
+        let accent = parse_var_with(var("ACCENT"), "ACCENT", parse_alerts)?.unwrap_or(vec![
+            Alert::Delay,
+            Alert::Cancellation,
+            Alert::PlatformChange,
+        ]);
+        let accent = if layout.tri_color { accent } else { Vec::new() };
+        let epd = parse_optional_var(var("EPD"), "EPD")?;
+        if epd.is_some() && layout.tri_color {
+            return Err(invalid(
+                "EPD",
+                String::from("the driver only supports black and white panels"),
+            ));
+        }
+        if epd.is_some() && (layout.width, layout.height) != (DISPLAY_WIDTH, DISPLAY_HEIGHT) {
+            return Err(invalid(
+                "EPD",
+                format!(
+                    "the 4.2\" panel needs a {}x{} LAYOUT",
+                    DISPLAY_WIDTH, DISPLAY_HEIGHT
+                ),
+            ));
+        }
+        let epd_simulator_file = var("EPD_SIMULATOR_FILE")
+            .unwrap_or(String::from(DEFAULT_EPD_SIMULATOR_FILE))
+            .into();
+        let epd_state_file = var("EPD_STATE_FILE")
+            .unwrap_or(String::from(DEFAULT_EPD_STATE_FILE))
+            .into();
+        let full_refresh_every = parse_var(
+            var("FULL_REFRESH_EVERY"),
+            "FULL_REFRESH_EVERY",
+            DEFAULT_FULL_REFRESH_EVERY,
+        )?;
+        if full_refresh_every == 0 {
+            return Err(invalid(
+                "FULL_REFRESH_EVERY",
+                String::from("must be at least 1"),
+            ));
+        }
+        let full_refresh_at = parse_var_with(var("FULL_REFRESH_AT"), "FULL_REFRESH_AT", |a| {
+            a.split(',').map(parse_hours_minutes).collect()
+        })?
+        .unwrap_or_default();
+
+        Ok(Config {
+            station_ids,
+            lines,
+            abbreviations,
+            destination_max_chars,
+            timezone,
+            transportations,
+            board_types,
+            board_datetime,
+            morning_preview,
+            horizon,
+            station_timeout,
+            log_level,
+            log_format,
+            update_interval,
+            metrics_address,
+            health_file,
+            health_max_age,
+            displayed_file,
+            forced_display_after,
+            image_file,
+            font_file,
+            layout,
+            line_icons,
+            badges,
+            accent,
+            epd,
+            epd_simulator_file,
+            epd_state_file,
+            full_refresh_every,
+            full_refresh_at,
+        })
+    }
+}
+
+/// Parses the value of the variable `name`, `default` when unset.
+fn parse_var<T: std::str::FromStr>(
+    value: Option<String>,
+    name: &str,
+    default: T,
+) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    Ok(parse_optional_var(value, name)?.unwrap_or(default))
+}
+
+/// Parses the value of the variable `name`, `None` when unset.
+fn parse_optional_var<T: std::str::FromStr>(
+    value: Option<String>,
+    name: &str,
+) -> Result<Option<T>, String>
+where
+    T::Err: std::fmt::Display,
+{
+    parse_var_with(value, name, |v| {
+        v.parse().map_err(|e: T::Err| e.to_string())
+    })
+}
+
+/// Parses the value of the variable `name` with `parse`, `None` when unset.
+fn parse_var_with<T>(
+    value: Option<String>,
+    name: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Option<T>, String> {
+    value
+        .map(|v| parse(v.trim()).map_err(|e| format!("invalid {}: {}", name, e)))
+        .transpose()
+}
+
+/// Parses a time of day written as `HH:MM`.
+fn parse_hours_minutes(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M").map_err(|_| String::from("expected HH:MM"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(vars: &[(&str, &str)]) -> Result<Config, String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Config::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn config_defaults() {
+        let c = config(&[("STATION_ID", "8587057"), ("LINES", "")]).unwrap();
+        assert_eq!(c.station_ids, vec!["8587057"]);
+        assert_eq!(c.lines, vec![]);
+        assert_eq!(
+            c.abbreviations,
+            parse_abbreviations(DEFAULT_ABBREVIATIONS).unwrap()
+        );
+        assert_eq!(c.destination_max_chars, DESTINATION_MAX_CHARS);
+        assert_eq!(c.timezone, DEFAULT_TIMEZONE);
+        assert_eq!(c.transportations, None);
+        assert_eq!(c.board_types, vec![BoardType::Departure]);
+        assert_eq!(c.board_datetime, None);
+        assert_eq!(c.morning_preview, None);
+        assert_eq!(
+            c.horizon,
+            chrono::Duration::minutes(DEFAULT_HORIZON_MINUTES)
+        );
+        assert_eq!(
+            c.station_timeout,
+            std::time::Duration::from_secs(DEFAULT_STATION_TIMEOUT_SECONDS)
+        );
+        assert_eq!(c.log_level, DEFAULT_LOG_LEVEL);
+        assert_eq!(c.log_format, LogFormat::Pretty);
+        assert_eq!(c.update_interval, None);
+        assert_eq!(c.metrics_address, None);
+        assert_eq!(c.health_file, std::path::Path::new(DEFAULT_HEALTH_FILE));
+        assert_eq!(
+            c.health_max_age,
+            chrono::Duration::minutes(DEFAULT_HEALTH_MAX_AGE_MINUTES)
+        );
+        assert_eq!(
+            c.displayed_file,
+            std::path::Path::new(DEFAULT_DISPLAYED_FILE)
+        );
+        assert_eq!(
+            c.forced_display_after,
+            chrono::Duration::minutes(DEFAULT_FORCED_DISPLAY_MINUTES)
+        );
+        assert_eq!(c.image_file, None);
+        assert_eq!(c.font_file, std::path::Path::new(DEFAULT_FONT_FILE));
+        assert_eq!(c.layout, Layout::default());
+        assert_eq!(c.badges, BadgeTable::default());
+        assert_eq!(c.accent, vec![]);
+        assert_eq!(c.epd, None);
+        assert_eq!(
+            c.epd_simulator_file,
+            std::path::Path::new(DEFAULT_EPD_SIMULATOR_FILE)
+        );
+        assert_eq!(
+            c.epd_state_file,
+            std::path::Path::new(DEFAULT_EPD_STATE_FILE)
+        );
+        assert_eq!(c.full_refresh_every, DEFAULT_FULL_REFRESH_EVERY);
+        assert_eq!(c.full_refresh_at, vec![]);
+
+        assert!(config(&[]).is_err());
+    }
+
+    #[test]
+    fn config_all_variables() {
+        let c = config(&[
+            ("STATION_ID", "8503000, 8587057"),
+            ("LINES", "3;6:Vernier"),
+            ("ABBREVIATIONS", "Bahnhof=Bhf"),
+            ("DESTINATION_MAX_CHARS", "12"),
+            ("TIMEZONE", "UTC"),
+            ("TRANSPORTATIONS", "bus,tram"),
+            ("BOARD_TYPE", "both"),
+            ("BOARD_DATETIME", "07:30"),
+            ("MORNING_PREVIEW_FROM", "21:00"),
+            ("MORNING_PREVIEW_AT", "06:00"),
+            ("HORIZON_MINUTES", "30"),
+            ("STATION_TIMEOUT_SECONDS", "5"),
+            ("RUST_LOG", "debug"),
+            ("LOG_LEVEL", "info"),
+            ("LOG_FORMAT", "JSON"),
+            ("UPDATE_INTERVAL_SECONDS", "60"),
+            ("METRICS_ADDRESS", "127.0.0.1:9898"),
+            ("HEALTH_FILE", "/run/api_fetcher/health.json"),
+            ("HEALTH_MAX_AGE_MINUTES", "5"),
+            ("DISPLAYED_FILE", "/run/api_fetcher/displayed.json"),
+            ("FORCED_DISPLAY_MINUTES", "0"),
+            ("IMAGE_FILE", "board.png"),
+            ("FONT_FILE", "DejaVuSansMono.ttf"),
+            ("LAYOUT", "7in5"),
+            ("ROWS", "7"),
+            ("HEADER_HEIGHT", "60"),
+            ("FONT_SIZE", "24.5"),
+            ("ROW_HEIGHT", "50"),
+            ("SEPARATOR_OFFSET", "40"),
+            ("SEPARATOR_THICKNESS", "0"),
+            ("LINE_COLUMN_WIDTH", "60"),
+            ("DELAY_COLUMN_WIDTH", "40"),
+            ("COLUMN_GAP", "8"),
+            ("HEADER_FONT_SIZE", "30"),
+            ("ICON_SIZE", "36"),
+            ("LINE_ICONS", "12=tram"),
+            ("BADGES", "swiss"),
+        ])
+        .unwrap();
+        assert_eq!(c.station_ids, vec!["8503000", "8587057"]);
+        assert_eq!(c.lines.len(), 2);
+        assert_eq!(
+            c.abbreviations,
+            vec![(String::from("Bahnhof"), String::from("Bhf"))]
+        );
+        assert_eq!(c.destination_max_chars, 12);
+        assert_eq!(c.timezone, chrono_tz::UTC);
+        assert_eq!(
+            c.transportations,
+            Some(vec![TransportMode::Bus, TransportMode::Tram])
+        );
+        assert_eq!(
+            c.board_types,
+            vec![BoardType::Departure, BoardType::Arrival]
+        );
+        assert_eq!(
+            c.board_datetime,
+            Some(BoardDateTime::Next(
+                NaiveTime::parse_from_str("07:30", "%H:%M").unwrap()
+            ))
+        );
+        assert!(c.morning_preview.is_some());
+        assert_eq!(c.horizon, chrono::Duration::minutes(30));
+        assert_eq!(c.station_timeout, std::time::Duration::from_secs(5));
+        assert_eq!(c.log_level, "info");
+        assert_eq!(c.log_format, LogFormat::Json);
+        assert_eq!(c.update_interval, Some(std::time::Duration::from_secs(60)));
+        assert_eq!(
+            c.metrics_address,
+            Some(std::net::SocketAddr::from(([127, 0, 0, 1], 9898)))
+        );
+        assert_eq!(
+            c.health_file,
+            std::path::Path::new("/run/api_fetcher/health.json")
+        );
+        assert_eq!(c.health_max_age, chrono::Duration::minutes(5));
+        assert_eq!(
+            c.displayed_file,
+            std::path::Path::new("/run/api_fetcher/displayed.json")
+        );
+        assert_eq!(c.forced_display_after, chrono::Duration::zero());
+        assert_eq!(c.image_file, Some(std::path::PathBuf::from("board.png")));
+        assert_eq!(c.font_file, std::path::Path::new("DejaVuSansMono.ttf"));
+        let preset = Layout::preset("7in5").unwrap();
+        assert_eq!(c.layout.rows, 7);
+        assert_eq!(c.layout.header_height, 60);
+        assert_eq!(
+            c.layout.style,
+            Style {
+                font_size: 24.5,
+                row_height: 50,
+                separator_offset: 40,
+                separator_thickness: 0,
+                line_width: 60,
+                delay_width: 40,
+                column_gap: 8,
+                header_font_size: 30.0,
+                icon_size: 36,
+                ..preset.style
+            }
+        );
+        assert_eq!(
+            c.line_icons,
+            vec![(String::from("12").parse().unwrap(), TransportMode::Tram)]
+        );
+        assert_eq!(c.badges, BadgeTable::load("swiss").unwrap());
+
+        let c = config(&[("STATION_ID", "8503000"), ("RUST_LOG", "debug")]).unwrap();
+        assert_eq!(c.log_level, "debug");
+
+        // all the alerts are in the accent color of tri-color layouts
+        let c = config(&[("STATION_ID", "8503000"), ("LAYOUT", "7in5bc")]).unwrap();
+        assert_eq!(c.accent.len(), 3);
+        let c = config(&[
+            ("STATION_ID", "8503000"),
+            ("LAYOUT", "7in5bc"),
+            ("ACCENT", "cancellations"),
+        ])
+        .unwrap();
+        assert_eq!(c.accent, vec![Alert::Cancellation]);
+
+        let c = config(&[
+            ("STATION_ID", "8503000"),
+            ("EPD", "Simulator"),
+            ("EPD_SIMULATOR_FILE", "panel.bmp"),
+            ("EPD_STATE_FILE", "/var/lib/api_fetcher/epd.json"),
+            ("FULL_REFRESH_EVERY", "1"),
+            ("FULL_REFRESH_AT", "03:00, 12:30"),
+        ])
+        .unwrap();
+        assert_eq!(c.epd, Some(EpdBackend::Simulator));
+        assert_eq!(c.epd_simulator_file, std::path::Path::new("panel.bmp"));
+        assert_eq!(
+            c.epd_state_file,
+            std::path::Path::new("/var/lib/api_fetcher/epd.json")
+        );
+        assert_eq!(c.full_refresh_every, 1);
+        assert_eq!(
+            c.full_refresh_at,
+            vec![
+                NaiveTime::from_hms_opt(3, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(12, 30, 0).unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn config_invalid_variables() {
+        for (name, value) in [
+            ("LINES", ":Onex"),
+            ("DESTINATION_MAX_CHARS", "-1"),
+            ("TIMEZONE", "Europe/Geneva"),
+            ("TRANSPORTATIONS", "hovercraft"),
+            ("BOARD_TYPE", "arrivals"),
+            ("BOARD_DATETIME", "tomorrow"),
+            ("MORNING_PREVIEW_AT", "6h"),
+            ("HORIZON_MINUTES", "two hours"),
+            ("STATION_ID", "8503000,"),
+            ("STATION_TIMEOUT_SECONDS", "1m"),
+            ("LOG_FORMAT", "syslog"),
+            ("UPDATE_INTERVAL_SECONDS", "1m"),
+            ("METRICS_ADDRESS", "localhost"),
+            ("HEALTH_MAX_AGE_MINUTES", "1h"),
+            ("FORCED_DISPLAY_MINUTES", "1h"),
+            ("FONT_SIZE", "large"),
+            ("ROW_HEIGHT", "-65"),
+            ("SEPARATOR_OFFSET", "47px"),
+            ("SEPARATOR_THICKNESS", "thin"),
+            ("LINE_COLUMN_WIDTH", "wide"),
+            ("DELAY_COLUMN_WIDTH", "-1"),
+            ("COLUMN_GAP", "1.5"),
+            ("HEADER_FONT_SIZE", "small"),
+            ("ICON_SIZE", "1.5"),
+            ("LINE_ICONS", "12:tram"),
+            ("BADGES", "tpg"),
+            ("ACCENT", "red"),
+            ("ROWS", "five"),
+            ("HEADER_HEIGHT", "-40"),
+            ("LAYOUT", "13in3"),
+            ("EPD", "spi"),
+            ("FULL_REFRESH_EVERY", "0"),
+            ("FULL_REFRESH_AT", "03:00;12:30"),
+        ] {
+            let e = config(&[
+                ("STATION_ID", "8587057"),
+                ("MORNING_PREVIEW_FROM", "21:00"),
+                (name, value),
+            ])
+            .unwrap_err();
+            assert!(e.contains(name), "{}", e);
+        }
+
+        // rows made too high for the layout
+        let e = config(&[("STATION_ID", "8587057"), ("ROW_HEIGHT", "80")]).unwrap_err();
+        assert!(e.contains("LAYOUT"), "{}", e);
+        // a header only fits with fewer rows
+        let e = config(&[("STATION_ID", "8587057"), ("HEADER_HEIGHT", "40")]).unwrap_err();
+        assert!(e.contains("LAYOUT"), "{}", e);
+        assert!(config(&[
+            ("STATION_ID", "8587057"),
+            ("HEADER_HEIGHT", "40"),
+            ("ROWS", "4")
+        ])
+        .is_ok());
+        // the driver only supports the 4.2" panel
+        let e = config(&[
+            ("STATION_ID", "8587057"),
+            ("LAYOUT", "7in5"),
+            ("EPD", "linux"),
+        ])
+        .unwrap_err();
+        assert!(e.contains("EPD"), "{}", e);
+        // and only in black and white
+        let e = config(&[
+            ("STATION_ID", "8587057"),
+            ("LAYOUT", "4in2b"),
+            ("EPD", "linux"),
+        ])
+        .unwrap_err();
+        assert!(e.contains("EPD"), "{}", e);
+    }
+}
@@ -0,0 +1,157 @@
+//! Datetime the board is shown at.
+
+use chrono::{DateTime, Days, NaiveDateTime, NaiveTime, TimeZone};
+use chrono_tz::Tz;
+
+/// Requested datetime of the board: either a full date and time, or a time of
+/// day standing for its next occurrence.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BoardDateTime {
+    At(NaiveDateTime),
+    Next(NaiveTime),
+}
+
+/// Parses `YYYY-MM-DD HH:MM`, `YYYY-MM-DDTHH:MM` or `HH:MM`.
+pub fn parse_board_datetime(s: &str) -> Result<BoardDateTime, String> {
+    let s = s.trim();
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M"))
+        .map(BoardDateTime::At)
+        .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M").map(BoardDateTime::Next))
+        .map_err(|_| {
+            format!(
+                "could not parse \"{}\", expected \"YYYY-MM-DD HH:MM\" or \"HH:MM\"",
+                s
+            )
+        })
+}
+
+/// Next occurrence of `time` after `now`, today or tomorrow.
+pub fn next_occurrence(now: DateTime<Tz>, time: NaiveTime) -> DateTime<Tz> {
+    let day = if now.time() < time {
+        now.date_naive()
+    } else {
+        now.date_naive() + Days::new(1)
+    };
+    localize(&now.timezone(), day.and_time(time))
+}
+
+/// Resolves `datetime` in `timezone`, taking the first of ambiguous times and
+/// skipping forward over times missing because of DST.
+pub fn localize(timezone: &Tz, datetime: NaiveDateTime) -> DateTime<Tz> {
+    timezone
+        .from_local_datetime(&datetime)
+        .earliest()
+        .unwrap_or_else(|| localize(timezone, datetime + chrono::Duration::hours(1)))
+}
+
+/// Datetime the board should be fetched at, `None` meaning now. A requested
+/// datetime has precedence over the morning preview `(from, at)`, which shows
+/// the departures of the next `at` between `from` and `at`.
+pub fn board_datetime(
+    now: DateTime<Tz>,
+    at: Option<BoardDateTime>,
+    morning_preview: Option<(NaiveTime, NaiveTime)>,
+) -> Option<DateTime<Tz>> {
+    match at {
+        Some(BoardDateTime::At(d)) => return Some(localize(&now.timezone(), d)),
+        Some(BoardDateTime::Next(t)) => return Some(next_occurrence(now, t)),
+        None => (),
+    }
+    let (from, at) = morning_preview?;
+    let t = now.time();
+    let in_preview = if from <= at {
+        from <= t && t < at
+    } else {
+        from <= t || t < at
+    };
+    in_preview.then(|| next_occurrence(now, at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_TIMEZONE;
+
+    fn zurich_time(s: &str) -> DateTime<Tz> {
+        localize(
+            &DEFAULT_TIMEZONE,
+            NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap(),
+        )
+    }
+
+    fn time(s: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(s, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn requested_board_datetime() {
+        let now = zurich_time("2024-04-19 22:15");
+        assert_eq!(board_datetime(now, None, None), None);
+        assert_eq!(
+            board_datetime(
+                now,
+                Some(parse_board_datetime("2024-04-21T07:30").unwrap()),
+                None
+            ),
+            Some(zurich_time("2024-04-21 07:30"))
+        );
+        assert_eq!(
+            board_datetime(now, Some(BoardDateTime::Next(time("23:00"))), None),
+            Some(zurich_time("2024-04-19 23:00"))
+        );
+        assert_eq!(
+            board_datetime(now, Some(BoardDateTime::Next(time("07:30"))), None),
+            Some(zurich_time("2024-04-20 07:30"))
+        );
+        // 02:30 does not exist on 2024-03-31 in Zurich
+        assert_eq!(
+            board_datetime(
+                now,
+                Some(parse_board_datetime("2024-03-31 02:30").unwrap()),
+                None
+            ),
+            Some(zurich_time("2024-03-31 03:30"))
+        );
+    }
+
+    #[test]
+    fn morning_preview_datetime() {
+        let preview = Some((time("21:00"), time("06:00")));
+        assert_eq!(
+            board_datetime(zurich_time("2024-04-19 20:59"), None, preview),
+            None
+        );
+        assert_eq!(
+            board_datetime(zurich_time("2024-04-19 21:00"), None, preview),
+            Some(zurich_time("2024-04-20 06:00"))
+        );
+        assert_eq!(
+            board_datetime(zurich_time("2024-04-20 01:30"), None, preview),
+            Some(zurich_time("2024-04-20 06:00"))
+        );
+        assert_eq!(
+            board_datetime(zurich_time("2024-04-20 06:00"), None, preview),
+            None
+        );
+        // a requested datetime wins over the preview
+        assert_eq!(
+            board_datetime(
+                zurich_time("2024-04-19 22:00"),
+                Some(BoardDateTime::Next(time("23:00"))),
+                preview
+            ),
+            Some(zurich_time("2024-04-19 23:00"))
+        );
+
+        let preview = Some((time("00:30"), time("05:00")));
+        assert_eq!(
+            board_datetime(zurich_time("2024-04-19 23:00"), None, preview),
+            None
+        );
+        assert_eq!(
+            board_datetime(zurich_time("2024-04-20 00:45"), None, preview),
+            Some(zurich_time("2024-04-20 05:00"))
+        );
+    }
+}
@@ -0,0 +1,155 @@
+//! Board last displayed, so that an unchanged board is not displayed again,
+//! sparing the e-paper and its refresh flash.
+
+use crate::format::{Header, LineInfo};
+use crate::health::{read_json, write_json};
+use crate::layout::Layout;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+
+/// Hash of the board last displayed and when, along with its lines and header
+/// to display it again marked stale when the following updates fail.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct Displayed {
+    pub hash: Option<u64>,
+    pub at: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub lines: Vec<LineInfo>,
+    pub header: Option<Header>,
+}
+
+/// Hash of what the display shows of `lines` and `header` drawn with
+/// `layout`, the update time of the header included as it is displayed, to
+/// the minute. The hash may differ between builds, only costing an extra
+/// display.
+pub fn board_hash(lines: &[LineInfo], header: Option<&Header>, layout: &Layout) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    lines.hash(&mut hasher);
+    header
+        .map(|h| (&h.station, &h.updated, h.stale, h.timetable_only, &h.failed))
+        .hash(&mut hasher);
+    // the layout has floating point sizes, hashed through their text
+    format!("{:?}", layout).hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Displayed {
+    /// Reads the file at `path`, nothing having been displayed yet when
+    /// missing.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        read_json(path)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        write_json(path, self)
+    }
+
+    /// Whether the board of `hash` needs displaying at `now`, being new or
+    /// displayed more than `forced_after` ago. A zero `forced_after` always
+    /// displays it.
+    pub fn needs_display(
+        &self,
+        hash: u64,
+        now: DateTime<Local>,
+        forced_after: chrono::Duration,
+    ) -> bool {
+        match self.at {
+            Some(at) if self.hash == Some(hash) => {
+                forced_after.is_zero() || now - at >= forced_after
+            }
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::local_time;
+
+    fn lines(delay: i32) -> Vec<LineInfo> {
+        vec![LineInfo {
+            line_number: String::from("6"),
+            direction: String::from("Vernier"),
+            normal_time: String::from("10:46"),
+            delay,
+            mode: None,
+            badge: None,
+            alerts: Vec::new(),
+        }]
+    }
+
+    fn header(updated: &str, stale: bool) -> Header {
+        Header {
+            station: String::from("Genève, g. Cornavin"),
+            updated: updated.to_owned(),
+            stale,
+            timetable_only: false,
+            failed: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn skip_unchanged() {
+        let layout = Layout::default();
+        let hash = board_hash(&lines(0), None, &layout);
+        assert_eq!(hash, board_hash(&lines(0), None, &layout));
+        assert_ne!(hash, board_hash(&lines(1), None, &layout));
+        assert_ne!(
+            hash,
+            board_hash(&lines(0), None, &Layout::preset("7in5").unwrap())
+        );
+        // the displayed update time changes the board
+        let fresh = board_hash(&lines(0), Some(&header("12:00", false)), &layout);
+        assert_ne!(hash, fresh);
+        assert_eq!(
+            fresh,
+            board_hash(&lines(0), Some(&header("12:00", false)), &layout)
+        );
+        assert_ne!(
+            fresh,
+            board_hash(&lines(0), Some(&header("12:01", false)), &layout)
+        );
+        assert_ne!(
+            fresh,
+            board_hash(&lines(0), Some(&header("12:00", true)), &layout)
+        );
+        let failed = Header {
+            failed: vec![String::from("8503000")],
+            ..header("12:00", false)
+        };
+        assert_ne!(fresh, board_hash(&lines(0), Some(&failed), &layout));
+
+        let t0 = local_time("2024-04-19T12:00:00+0200");
+        let hour = chrono::Duration::minutes(60);
+        assert!(Displayed::default().needs_display(hash, t0, hour));
+        let displayed = Displayed {
+            hash: Some(hash),
+            at: Some(t0),
+            lines: lines(0),
+            header: None,
+        };
+        let t1 = t0 + chrono::Duration::minutes(59);
+        assert!(!displayed.needs_display(hash, t1, hour));
+        assert!(displayed.needs_display(hash + 1, t1, hour));
+        assert!(displayed.needs_display(hash, t0 + hour, hour));
+        assert!(displayed.needs_display(hash, t1, chrono::Duration::zero()));
+    }
+
+    #[test]
+    fn write_read() {
+        let path = std::env::temp_dir().join(format!("displayed_{}.json", std::process::id()));
+        assert_eq!(Displayed::read(&path), Ok(Displayed::default()));
+        let displayed = Displayed {
+            hash: Some(42),
+            at: Some(local_time("2024-04-19T12:00:00+0200")),
+            lines: lines(1),
+            header: Some(header("12:00", false)),
+        };
+        displayed.write(&path).unwrap();
+        assert_eq!(Displayed::read(&path), Ok(displayed));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,446 @@
+//! Driver of the 4.2" Waveshare e-paper panel over the `embedded-hal` SPI and
+//! GPIO traits, a port of the vendored `epd4in2` Python module. It drives the
+//! panel of a Raspberry Pi through Linux spidev and gpio-cdev with the
+//! `epd-linux` feature on Linux, or the [`simulator`] for development and
+//! tests.
+
+pub mod refresh;
+pub mod simulator;
+
+use crate::render::{Frame, Region, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::spi::Write;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use refresh::Refresh;
+use std::fmt::Debug;
+
+pub const EPD_WIDTH: u32 = DISPLAY_WIDTH;
+pub const EPD_HEIGHT: u32 = DISPLAY_HEIGHT;
+/// Longest wait for the panel to be idle, a full refresh taking about 4s.
+pub const BUSY_TIMEOUT_MS: u32 = 30_000;
+const BUSY_POLL_MS: u32 = 100;
+/// Largest SPI transfer, the default buffer size of spidev.
+const SPI_CHUNK_SIZE: usize = 4096;
+
+pub const PANEL_SETTING: u8 = 0x00;
+pub const POWER_SETTING: u8 = 0x01;
+pub const POWER_OFF: u8 = 0x02;
+pub const POWER_ON: u8 = 0x04;
+pub const BOOSTER_SOFT_START: u8 = 0x06;
+pub const DEEP_SLEEP: u8 = 0x07;
+pub const DATA_START_TRANSMISSION_1: u8 = 0x10;
+pub const DISPLAY_REFRESH: u8 = 0x12;
+pub const DATA_START_TRANSMISSION_2: u8 = 0x13;
+pub const LUT_VCOM: u8 = 0x20;
+pub const LUT_WW: u8 = 0x21;
+pub const LUT_BW: u8 = 0x22;
+pub const LUT_WB: u8 = 0x23;
+pub const LUT_BB: u8 = 0x24;
+pub const PLL_CONTROL: u8 = 0x30;
+/// Data of [`VCOM_AND_DATA_INTERVAL_SETTING`] for full refreshes with a white
+/// border, partial ones inverting the [`DATA_POLARITY`].
+pub const FULL_DATA_INTERVAL: u8 = 0x97;
+pub const PARTIAL_DATA_INTERVAL: u8 = 0x07;
+/// Data polarity bit of [`VCOM_AND_DATA_INTERVAL_SETTING`].
+pub const DATA_POLARITY: u8 = 0x10;
+pub const VCOM_AND_DATA_INTERVAL_SETTING: u8 = 0x50;
+pub const RESOLUTION_SETTING: u8 = 0x61;
+pub const GET_STATUS: u8 = 0x71;
+pub const VCM_DC_SETTING: u8 = 0x82;
+pub const PARTIAL_WINDOW: u8 = 0x90;
+pub const PARTIAL_IN: u8 = 0x91;
+pub const PARTIAL_OUT: u8 = 0x92;
+// the panel needs 200µs at least after a partial refresh before polling
+const PARTIAL_REFRESH_DELAY_MS: u32 = 200;
+/// Data of [`DEEP_SLEEP`], guarding against entering it by mistake.
+pub const DEEP_SLEEP_CHECK: u8 = 0xa5;
+
+const LUT_VCOM0: [u8; 44] = [
+    0x00, 0x08, 0x08, 0x00, 0x00, 0x02, 0x00, 0x0f, 0x0f, 0x00, 0x00, 0x01, 0x00, 0x08, 0x08, 0x00,
+    0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+const LUT_WW0: [u8; 42] = [
+    0x50, 0x08, 0x08, 0x00, 0x00, 0x02, 0x90, 0x0f, 0x0f, 0x00, 0x00, 0x01, 0xa0, 0x08, 0x08, 0x00,
+    0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+const LUT_BW0: [u8; 42] = LUT_WW0;
+const LUT_WB0: [u8; 42] = [
+    0xa0, 0x08, 0x08, 0x00, 0x00, 0x02, 0x90, 0x0f, 0x0f, 0x00, 0x00, 0x01, 0x50, 0x08, 0x08, 0x00,
+    0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+const LUT_BB0: [u8; 42] = [
+    0x20, 0x08, 0x08, 0x00, 0x00, 0x02, 0x90, 0x0f, 0x0f, 0x00, 0x00, 0x01, 0x10, 0x08, 0x08, 0x00,
+    0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+const LUT_VCOM1: [u8; 60] = partial_lut(0x00);
+const LUT_WW1: [u8; 42] = partial_lut(0x00);
+const LUT_BW1: [u8; 60] = partial_lut(0x20);
+const LUT_WB1: [u8; 42] = partial_lut(0x10);
+const LUT_BB1: [u8; 42] = partial_lut(0x00);
+
+/// Partial refresh LUT, a single phase starting with `level`.
+const fn partial_lut<const N: usize>(level: u8) -> [u8; N] {
+    let mut lut = [0; N];
+    lut[0] = level;
+    lut[1] = 0x01;
+    lut[2] = 0x20;
+    lut[3] = 0x01;
+    lut[5] = 0x01;
+    lut
+}
+
+/// The 4.2" panel, wired through `spi` with its chip select, and the
+/// data/command `dc`, `reset` and `busy` pins.
+pub struct Epd4in2<SPI, DC, RST, BUSY, DELAY> {
+    spi: SPI,
+    dc: DC,
+    reset: RST,
+    busy: BUSY,
+    delay: DELAY,
+}
+
+impl<SPI, DC, RST, BUSY, DELAY> Epd4in2<SPI, DC, RST, BUSY, DELAY>
+where
+    SPI: Write<u8>,
+    SPI::Error: Debug,
+    DC: OutputPin,
+    DC::Error: Debug,
+    RST: OutputPin,
+    RST::Error: Debug,
+    BUSY: InputPin,
+    BUSY::Error: Debug,
+    DELAY: DelayMs<u32>,
+{
+    pub fn new(spi: SPI, dc: DC, reset: RST, busy: BUSY, delay: DELAY) -> Self {
+        Epd4in2 {
+            spi,
+            dc,
+            reset,
+            busy,
+            delay,
+        }
+    }
+
+    /// Wakes the panel up and powers it on for a full refresh.
+    pub fn init(&mut self) -> Result<(), String> {
+        self.power_on(FULL_DATA_INTERVAL)?;
+        self.set_lut()
+    }
+
+    /// Wakes the panel up and powers it on for partial refreshes.
+    pub fn init_partial(&mut self) -> Result<(), String> {
+        self.power_on(PARTIAL_DATA_INTERVAL)?;
+        self.command_data(LUT_VCOM, &LUT_VCOM1)?;
+        self.command_data(LUT_WW, &LUT_WW1)?;
+        self.command_data(LUT_BW, &LUT_BW1)?;
+        self.command_data(LUT_WB, &LUT_WB1)?;
+        self.command_data(LUT_BB, &LUT_BB1)
+    }
+
+    fn power_on(&mut self, data_interval: u8) -> Result<(), String> {
+        self.hardware_reset()?;
+        self.command_data(POWER_SETTING, &[0x03, 0x00, 0x2b, 0x2b])?;
+        self.command_data(BOOSTER_SOFT_START, &[0x17, 0x17, 0x17])?;
+        self.command(POWER_ON)?;
+        self.wait_until_idle()?;
+        self.command_data(PANEL_SETTING, &[0xbf])?;
+        // 100Hz
+        self.command_data(PLL_CONTROL, &[0x3c])?;
+        let [_, _, w1, w0] = EPD_WIDTH.to_be_bytes();
+        let [_, _, h1, h0] = EPD_HEIGHT.to_be_bytes();
+        self.command_data(RESOLUTION_SETTING, &[w1, w0, h1, h0])?;
+        self.command_data(VCM_DC_SETTING, &[0x12])?;
+        self.command_data(VCOM_AND_DATA_INTERVAL_SETTING, &[data_interval])
+    }
+
+    /// Fully refreshes the panel with `frame`, flashing it.
+    pub fn display(&mut self, frame: &Frame) -> Result<(), String> {
+        if (frame.width, frame.height) != (EPD_WIDTH, EPD_HEIGHT) {
+            return Err(format!(
+                "the panel displays {}x{} frames, not {}x{}",
+                EPD_WIDTH, EPD_HEIGHT, frame.width, frame.height
+            ));
+        }
+        self.command(PARTIAL_OUT)?;
+        self.set_lut()?;
+        let white = vec![0xff; (EPD_WIDTH / 8 * EPD_HEIGHT) as usize];
+        self.command_data(DATA_START_TRANSMISSION_1, &white)?;
+        self.command_data(DATA_START_TRANSMISSION_2, &pack(frame))?;
+        self.command(DISPLAY_REFRESH)?;
+        self.wait_until_idle()
+    }
+
+    /// Refreshes the `region` of the panel from `previous` to `frame` without
+    /// flashing it, after [`init_partial`](Epd4in2::init_partial). The region
+    /// is widened to whole bytes.
+    pub fn display_partial(
+        &mut self,
+        previous: &Frame,
+        frame: &Frame,
+        region: Region,
+    ) -> Result<(), String> {
+        for f in [previous, frame] {
+            if (f.width, f.height) != (EPD_WIDTH, EPD_HEIGHT) {
+                return Err(format!(
+                    "the panel displays {}x{} frames, not {}x{}",
+                    EPD_WIDTH, EPD_HEIGHT, f.width, f.height
+                ));
+            }
+        }
+        if region.x1 > EPD_WIDTH || region.y1 > EPD_HEIGHT || region.area() == 0 {
+            return Err(format!("invalid partial refresh region {:?}", region));
+        }
+        let (x0, x1) = (region.x0 / 8 * 8, region.x1.div_ceil(8) * 8);
+        let [_, _, x0_1, x0_0] = x0.to_be_bytes();
+        let [_, _, x1_1, x1_0] = (x1 - 1).to_be_bytes();
+        let [_, _, y0_1, y0_0] = region.y0.to_be_bytes();
+        let [_, _, y1_1, y1_0] = (region.y1 - 1).to_be_bytes();
+        self.command(PARTIAL_IN)?;
+        self.command_data(
+            PARTIAL_WINDOW,
+            // the last byte scans the window only
+            &[x0_1, x0_0, x1_1, x1_0, y0_1, y0_0, y1_1, y1_0, 0x28],
+        )?;
+        let stride = (EPD_WIDTH / 8) as usize;
+        let window = |f: &Frame| -> Vec<u8> {
+            pack(f)
+                .chunks(stride)
+                .skip(region.y0 as usize)
+                .take((region.y1 - region.y0) as usize)
+                .flat_map(|row| &row[x0 as usize / 8..x1 as usize / 8])
+                .map(|b| !b)
+                .collect()
+        };
+        self.command_data(DATA_START_TRANSMISSION_1, &window(previous))?;
+        self.command_data(DATA_START_TRANSMISSION_2, &window(frame))?;
+        self.command(DISPLAY_REFRESH)?;
+        self.delay.delay_ms(PARTIAL_REFRESH_DELAY_MS);
+        self.wait_until_idle()
+    }
+
+    /// Refreshes the panel from `previous` to `frame` as planned by `refresh`,
+    /// from and back to deep sleep.
+    pub fn refresh(
+        &mut self,
+        refresh: &Refresh,
+        previous: Option<&Frame>,
+        frame: &Frame,
+    ) -> Result<(), String> {
+        match (refresh, previous) {
+            (Refresh::Skip, _) => Ok(()),
+            (Refresh::Full, _) => self.show(frame),
+            (Refresh::Partial(regions), Some(previous)) => {
+                self.init_partial()?;
+                for r in regions {
+                    self.display_partial(previous, frame, *r)?;
+                }
+                self.sleep()
+            }
+            (Refresh::Partial(_), None) => Err(String::from(
+                "a partial refresh needs the previously displayed frame",
+            )),
+        }
+    }
+
+    /// Powers the panel off into deep sleep, which only a reset leaves.
+    pub fn sleep(&mut self) -> Result<(), String> {
+        self.command(POWER_OFF)?;
+        self.wait_until_idle()?;
+        self.command_data(DEEP_SLEEP, &[DEEP_SLEEP_CHECK])
+    }
+
+    /// Displays `frame` from and back to deep sleep, as after each update.
+    pub fn show(&mut self, frame: &Frame) -> Result<(), String> {
+        self.init()?;
+        self.display(frame)?;
+        self.sleep()
+    }
+
+    fn hardware_reset(&mut self) -> Result<(), String> {
+        self.set_reset(true)?;
+        for _ in 0..3 {
+            self.set_reset(false)?;
+            self.set_reset(true)?;
+        }
+        Ok(())
+    }
+
+    fn set_reset(&mut self, high: bool) -> Result<(), String> {
+        if high {
+            self.reset.set_high()
+        } else {
+            self.reset.set_low()
+        }
+        .map_err(|e| format!("could not set the reset pin: {:?}", e))?;
+        self.delay.delay_ms(10);
+        Ok(())
+    }
+
+    fn set_lut(&mut self) -> Result<(), String> {
+        self.command_data(LUT_VCOM, &LUT_VCOM0)?;
+        self.command_data(LUT_WW, &LUT_WW0)?;
+        self.command_data(LUT_BW, &LUT_BW0)?;
+        // swapped as in the vendor driver
+        self.command_data(LUT_WB, &LUT_BB0)?;
+        self.command_data(LUT_BB, &LUT_WB0)
+    }
+
+    /// Polls the busy pin, low while the panel is busy.
+    fn wait_until_idle(&mut self) -> Result<(), String> {
+        let mut waited = 0;
+        self.command(GET_STATUS)?;
+        while self
+            .busy
+            .is_low()
+            .map_err(|e| format!("could not read the busy pin: {:?}", e))?
+        {
+            if waited >= BUSY_TIMEOUT_MS {
+                return Err(format!("panel still busy after {}ms", waited));
+            }
+            self.command(GET_STATUS)?;
+            self.delay.delay_ms(BUSY_POLL_MS);
+            waited += BUSY_POLL_MS;
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, command: u8) -> Result<(), String> {
+        self.dc
+            .set_low()
+            .map_err(|e| format!("could not set the data/command pin: {:?}", e))?;
+        self.spi
+            .write(&[command])
+            .map_err(|e| format!("could not send command {:#04x}: {:?}", command, e))
+    }
+
+    fn command_data(&mut self, command: u8, data: &[u8]) -> Result<(), String> {
+        self.command(command)?;
+        self.dc
+            .set_high()
+            .map_err(|e| format!("could not set the data/command pin: {:?}", e))?;
+        for chunk in data.chunks(SPI_CHUNK_SIZE) {
+            self.spi.write(chunk).map_err(|e| {
+                format!(
+                    "could not send the data of command {:#04x}: {:?}",
+                    command, e
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Packs `frame` 8 pixels a byte as the panel expects, the most significant
+/// bit first and white pixels being set.
+pub fn pack(frame: &Frame) -> Vec<u8> {
+    let stride = frame.width.div_ceil(8) as usize;
+    let mut bytes = vec![0xff; stride * frame.height as usize];
+    for y in 0..frame.height as i32 {
+        for x in 0..frame.width as i32 {
+            if frame.get(x, y) {
+                bytes[y as usize * stride + x as usize / 8] &= !(0x80 >> (x % 8));
+            }
+        }
+    }
+    bytes
+}
+
+/// Frame of `width` by `height` pixels packed in `bytes`, see [`pack`].
+pub fn unpack(width: u32, height: u32, bytes: &[u8]) -> Result<Frame, String> {
+    let stride = width.div_ceil(8) as usize;
+    if bytes.len() != stride * height as usize {
+        return Err(format!(
+            "{} bytes do not pack a {}x{} frame",
+            bytes.len(),
+            width,
+            height
+        ));
+    }
+    let mut frame = Frame::new(width, height);
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            if bytes[y as usize * stride + x as usize / 8] & (0x80 >> (x % 8)) == 0 {
+                frame.set(x, y, true);
+            }
+        }
+    }
+    Ok(frame)
+}
+
+#[cfg(all(feature = "epd-linux", target_os = "linux"))]
+pub use linux::{open_linux, LinuxEpd, GPIO_CHIP, SPI_DEVICE};
+
+#[cfg(all(feature = "epd-linux", target_os = "linux"))]
+mod linux {
+    use super::Epd4in2;
+    use linux_embedded_hal::gpio_cdev::{Chip, LineRequestFlags};
+    use linux_embedded_hal::spidev::{SpiModeFlags, SpidevOptions};
+    use linux_embedded_hal::{CdevPin, Delay, Spidev};
+
+    pub const SPI_DEVICE: &str = "/dev/spidev0.0";
+    pub const GPIO_CHIP: &str = "/dev/gpiochip0";
+    const SPI_SPEED_HZ: u32 = 4_000_000;
+    // pins of the Waveshare e-paper HAT, in BCM numbering
+    const RST_PIN: u32 = 17;
+    const DC_PIN: u32 = 25;
+    const BUSY_PIN: u32 = 24;
+    const PWR_PIN: u32 = 18;
+    const CONSUMER: &str = "api_fetcher";
+
+    /// The panel of a Raspberry Pi e-paper HAT, powered while held.
+    pub struct LinuxEpd {
+        pub epd: Epd4in2<Spidev, CdevPin, CdevPin, CdevPin, Delay>,
+        _power: CdevPin,
+    }
+
+    /// Opens the panel of the e-paper HAT on [`SPI_DEVICE`] and [`GPIO_CHIP`].
+    pub fn open_linux() -> Result<LinuxEpd, String> {
+        let mut spi = Spidev::open(SPI_DEVICE)
+            .map_err(|e| format!("could not open {}: {}", SPI_DEVICE, e))?;
+        spi.configure(
+            &SpidevOptions::new()
+                .bits_per_word(8)
+                .max_speed_hz(SPI_SPEED_HZ)
+                .mode(SpiModeFlags::SPI_MODE_0)
+                .build(),
+        )
+        .map_err(|e| format!("could not configure {}: {}", SPI_DEVICE, e))?;
+        let mut chip =
+            Chip::new(GPIO_CHIP).map_err(|e| format!("could not open {}: {}", GPIO_CHIP, e))?;
+        let mut pin = |offset: u32, flags: LineRequestFlags, value: u8| {
+            chip.get_line(offset)
+                .and_then(|l| l.request(flags, value, CONSUMER))
+                .and_then(CdevPin::new)
+                .map_err(|e| format!("could not request GPIO {}: {}", offset, e))
+        };
+        let power = pin(PWR_PIN, LineRequestFlags::OUTPUT, 1)?;
+        let reset = pin(RST_PIN, LineRequestFlags::OUTPUT, 1)?;
+        let dc = pin(DC_PIN, LineRequestFlags::OUTPUT, 0)?;
+        let busy = pin(BUSY_PIN, LineRequestFlags::INPUT, 0)?;
+        Ok(LinuxEpd {
+            epd: Epd4in2::new(spi, dc, reset, busy, Delay),
+            _power: power,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_unpack() {
+        let mut frame = Frame::new(12, 2);
+        frame.set(0, 0, true);
+        frame.set(9, 0, true);
+        frame.set(11, 1, true);
+        let bytes = pack(&frame);
+        assert_eq!(bytes, vec![0b0111_1111, 0b1011_1111, 0xff, 0b1110_1111]);
+        assert_eq!(unpack(12, 2, &bytes), Ok(frame));
+        assert!(unpack(12, 3, &bytes).is_err());
+    }
+}
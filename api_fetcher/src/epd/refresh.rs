@@ -0,0 +1,244 @@
+//! Choice between full and partial refreshes of the panel, partial ones not
+//! flashing it but leaving ghosts that full ones regularly clear.
+
+use crate::health::{read_json, write_json};
+use crate::render::{Frame, Region};
+use chrono::{DateTime, Local, NaiveTime};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// changes larger than this share of the panel are fully refreshed
+const PARTIAL_MAX_AREA_PERCENT: u32 = 50;
+
+/// How the panel is refreshed with a new frame.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Refresh {
+    /// Nothing changed.
+    Skip,
+    /// Flashing the whole panel, clearing its ghosts.
+    Full,
+    /// Only the changed regions.
+    Partial(Vec<Region>),
+}
+
+/// When full refreshes are forced.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RefreshPolicy {
+    /// One refresh in `every` is full, partial refreshes being disabled at 1.
+    pub every: u32,
+    /// Times in `timezone` after which the next refresh is full.
+    pub at: Vec<NaiveTime>,
+    pub timezone: Tz,
+}
+
+/// Refreshes since the last full one, kept between updates.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct RefreshState {
+    pub last_full_refresh: Option<DateTime<Local>>,
+    pub partial_refreshes: u32,
+}
+
+impl RefreshPolicy {
+    /// Refresh from `previous`, the frame displayed if known, to `frame`.
+    pub fn plan(
+        &self,
+        state: &RefreshState,
+        previous: Option<&Frame>,
+        frame: &Frame,
+        now: DateTime<Local>,
+    ) -> Refresh {
+        let Some(previous) = previous else {
+            return Refresh::Full;
+        };
+        if state.partial_refreshes + 1 >= self.every || self.full_refresh_due(state, now) {
+            return Refresh::Full;
+        }
+        let regions = frame.changed_regions(previous);
+        let area: u32 = regions.iter().map(Region::area).sum();
+        if regions.is_empty() {
+            Refresh::Skip
+        } else if area * 100 > frame.width * frame.height * PARTIAL_MAX_AREA_PERCENT {
+            Refresh::Full
+        } else {
+            Refresh::Partial(regions)
+        }
+    }
+
+    /// Whether one of the `at` times passed since the last full refresh.
+    fn full_refresh_due(&self, state: &RefreshState, now: DateTime<Local>) -> bool {
+        let Some(last) = state.last_full_refresh else {
+            return true;
+        };
+        let now = now.with_timezone(&self.timezone).naive_local();
+        let last = last.with_timezone(&self.timezone).naive_local();
+        self.at.iter().any(|t| {
+            let mut at = now.date().and_time(*t);
+            if at > now {
+                at -= chrono::Duration::days(1);
+            }
+            at > last
+        })
+    }
+}
+
+impl RefreshState {
+    /// Reads the state file at `path`, the panel not having been refreshed
+    /// yet when missing.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        read_json(path)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        write_json(path, self)
+    }
+
+    /// State after `refresh` at `now`.
+    pub fn record(self, refresh: &Refresh, now: DateTime<Local>) -> Self {
+        match refresh {
+            Refresh::Skip => self,
+            Refresh::Full => RefreshState {
+                last_full_refresh: Some(now),
+                partial_refreshes: 0,
+            },
+            Refresh::Partial(_) => RefreshState {
+                partial_refreshes: self.partial_refreshes + 1,
+                ..self
+            },
+        }
+    }
+}
+
+/// Image file of the frame displayed, kept next to the state file at `path`.
+pub fn displayed_frame_file(path: &Path) -> PathBuf {
+    path.with_extension("png")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DEFAULT_TIMEZONE;
+    use crate::fixtures::local_time;
+
+    fn frames() -> (Frame, Frame) {
+        let previous = Frame::new(400, 300);
+        let mut frame = previous.clone();
+        frame.fill_rect((350, 130), (390, 170), true);
+        (previous, frame)
+    }
+
+    #[test]
+    fn plan_refreshes() {
+        let policy = RefreshPolicy {
+            every: 3,
+            at: Vec::new(),
+            timezone: DEFAULT_TIMEZONE,
+        };
+        let t0 = local_time("2024-04-19T12:00:00+0200");
+        let (previous, frame) = frames();
+
+        let state = RefreshState::default();
+        assert_eq!(policy.plan(&state, None, &frame, t0), Refresh::Full);
+        let state = state.record(&Refresh::Full, t0);
+        let refresh = policy.plan(&state, Some(&previous), &frame, t0);
+        assert_eq!(
+            refresh,
+            Refresh::Partial(vec![Region {
+                x0: 350,
+                y0: 130,
+                x1: 391,
+                y1: 171
+            }])
+        );
+        assert_eq!(policy.plan(&state, Some(&frame), &frame, t0), Refresh::Skip);
+        // one refresh in 3 is full
+        let state = state.record(&refresh, t0);
+        assert_eq!(policy.plan(&state, Some(&previous), &frame, t0), refresh);
+        let state = state.record(&refresh, t0);
+        assert_eq!(state.partial_refreshes, 2);
+        assert_eq!(
+            policy.plan(&state, Some(&previous), &frame, t0),
+            Refresh::Full
+        );
+
+        let mut black = previous.clone();
+        black.fill_rect((0, 0), (399, 200), true);
+        let state = state.record(&Refresh::Full, t0);
+        assert_eq!(
+            policy.plan(&state, Some(&previous), &black, t0),
+            Refresh::Full
+        );
+    }
+
+    #[test]
+    fn full_refresh_at() {
+        let policy = RefreshPolicy {
+            every: 100,
+            at: vec![NaiveTime::from_hms_opt(3, 0, 0).unwrap()],
+            timezone: DEFAULT_TIMEZONE,
+        };
+        // the times are in the configured timezone, whatever the one of the
+        // tests
+        let local = |s| {
+            chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M")
+                .unwrap()
+                .and_local_timezone(DEFAULT_TIMEZONE)
+                .unwrap()
+                .with_timezone(&Local)
+        };
+        let (previous, frame) = frames();
+        let state = RefreshState::default().record(&Refresh::Full, local("2024-04-19 02:00"));
+        let plan = |now| policy.plan(&state, Some(&previous), &frame, local(now));
+        assert!(matches!(plan("2024-04-19 02:59"), Refresh::Partial(_)));
+        assert_eq!(plan("2024-04-19 03:00"), Refresh::Full);
+        assert_eq!(plan("2024-04-20 01:00"), Refresh::Full);
+    }
+
+    #[test]
+    fn full_refresh_at_in_timezone() {
+        // 03:00 in Kathmandu, UTC+05:45, is 21:15 UTC the day before
+        let policy = RefreshPolicy {
+            every: 100,
+            at: vec![NaiveTime::from_hms_opt(3, 0, 0).unwrap()],
+            timezone: chrono_tz::Asia::Kathmandu,
+        };
+        let (previous, frame) = frames();
+        let state =
+            RefreshState::default().record(&Refresh::Full, local_time("2024-04-18T20:00:00+0000"));
+        let plan = |now| policy.plan(&state, Some(&previous), &frame, local_time(now));
+        assert!(matches!(
+            plan("2024-04-18T21:14:00+0000"),
+            Refresh::Partial(_)
+        ));
+        assert_eq!(plan("2024-04-18T21:15:00+0000"), Refresh::Full);
+
+        // 03:00 UTC is not a full refresh time there
+        let state =
+            RefreshState::default().record(&Refresh::Full, local_time("2024-04-18T22:00:00+0000"));
+        assert!(matches!(
+            policy.plan(
+                &state,
+                Some(&previous),
+                &frame,
+                local_time("2024-04-19T03:30:00+0000")
+            ),
+            Refresh::Partial(_)
+        ));
+    }
+
+    #[test]
+    fn write_read() {
+        let path = std::env::temp_dir().join(format!("refresh_{}.json", std::process::id()));
+        assert_eq!(RefreshState::read(&path), Ok(RefreshState::default()));
+        let state = RefreshState::default()
+            .record(&Refresh::Full, local_time("2024-04-19T12:00:00+0200"))
+            .record(
+                &Refresh::Partial(Vec::new()),
+                local_time("2024-04-19T12:01:00+0200"),
+            );
+        state.write(&path).unwrap();
+        assert_eq!(RefreshState::read(&path), Ok(state));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(displayed_frame_file(&path).extension().unwrap(), "png");
+    }
+}
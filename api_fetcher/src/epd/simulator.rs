@@ -0,0 +1,401 @@
+//! Software panel standing in for the 4.2" e-paper panel, recording the
+//! commands it receives and producing the image a refresh would display.
+
+use super::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Command received by the simulated panel with its data.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Command {
+    pub code: u8,
+    pub data: Vec<u8>,
+}
+
+/// Error of the simulated panel, when driven against its protocol.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SimulatorError(pub String);
+
+#[derive(Default)]
+struct Panel {
+    data_mode: bool,
+    reset_high: bool,
+    asleep: bool,
+    /// Busy polls left before the panel is idle.
+    busy_polls: u32,
+    commands: Vec<Command>,
+    /// Image written for the next refresh, lost in deep sleep.
+    ram: Option<Frame>,
+    ram_cursor: u32,
+    /// Inverted data polarity, as for partial refreshes.
+    inverted: bool,
+    /// Window of the partial mode, `None` outside of it.
+    window: Option<Region>,
+    /// Image on the panel, kept without power.
+    frame: Option<Frame>,
+    refreshes: usize,
+    partial_refreshes: usize,
+    delay_ms: u64,
+}
+
+impl Panel {
+    fn receive(&mut self, bytes: &[u8]) -> Result<(), SimulatorError> {
+        if self.asleep {
+            return Err(SimulatorError(String::from(
+                "received SPI bytes while in deep sleep",
+            )));
+        }
+        if !self.data_mode {
+            for &code in bytes {
+                self.command(code)?;
+            }
+            return Ok(());
+        }
+        let command = self.commands.last_mut().ok_or(SimulatorError(String::from(
+            "received data before a command",
+        )))?;
+        command.data.extend_from_slice(bytes);
+        let word = |h: u8, l: u8| u32::from_be_bytes([0, 0, h, l]);
+        match (command.code, command.data.as_slice()) {
+            (RESOLUTION_SETTING, &[w1, w0, h1, h0]) => {
+                self.ram = Some(Frame::new(word(w1, w0), word(h1, h0)));
+            }
+            (VCOM_AND_DATA_INTERVAL_SETTING, &[d]) => self.inverted = d & DATA_POLARITY == 0,
+            (PARTIAL_WINDOW, &[x0_1, x0_0, x1_1, x1_0, y0_1, y0_0, y1_1, y1_0, _]) => {
+                self.window = Some(Region {
+                    x0: word(x0_1, x0_0),
+                    y0: word(y0_1, y0_0),
+                    x1: word(x1_1, x1_0) + 1,
+                    y1: word(y1_1, y1_0) + 1,
+                });
+            }
+            (DATA_START_TRANSMISSION_2, _) => {
+                for &b in bytes {
+                    self.write_ram(b)?;
+                }
+            }
+            (DEEP_SLEEP, &[DEEP_SLEEP_CHECK]) => {
+                self.asleep = true;
+                self.ram = None;
+                self.window = None;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Writes the next byte of image in the RAM, within the partial window.
+    fn write_ram(&mut self, byte: u8) -> Result<(), SimulatorError> {
+        let ram = self.ram.as_mut().ok_or(SimulatorError(String::from(
+            "received an image before the resolution",
+        )))?;
+        let window = self.window.unwrap_or(Region {
+            x0: 0,
+            y0: 0,
+            x1: ram.width,
+            y1: ram.height,
+        });
+        let bytes_per_row = (window.x1 - window.x0).div_ceil(8);
+        let (x, y) = (
+            window.x0 + self.ram_cursor % bytes_per_row * 8,
+            window.y0 + self.ram_cursor / bytes_per_row,
+        );
+        if y >= window.y1 {
+            return Err(SimulatorError(format!(
+                "received more image than the {:?} window",
+                window
+            )));
+        }
+        let byte = if self.inverted { !byte } else { byte };
+        for i in 0..8 {
+            ram.set((x + i) as i32, y as i32, byte & (0x80 >> i) == 0);
+        }
+        self.ram_cursor += 1;
+        Ok(())
+    }
+
+    /// Displays the RAM, within the partial window only.
+    fn refresh(&mut self) -> Result<(), SimulatorError> {
+        let ram = self.ram.as_ref().ok_or(SimulatorError(String::from(
+            "refreshed before setting the resolution",
+        )))?;
+        match (self.window, &mut self.frame) {
+            (Some(w), Some(frame)) => {
+                for y in w.y0..w.y1 {
+                    for x in w.x0..w.x1 {
+                        frame.set(x as i32, y as i32, ram.get(x as i32, y as i32));
+                    }
+                }
+                self.partial_refreshes += 1;
+            }
+            (Some(_), None) => {
+                return Err(SimulatorError(String::from(
+                    "partially refreshed a panel never fully refreshed",
+                )))
+            }
+            (None, _) => {
+                self.frame = Some(ram.clone());
+                self.refreshes += 1;
+            }
+        }
+        self.busy_polls = 3;
+        Ok(())
+    }
+
+    fn command(&mut self, code: u8) -> Result<(), SimulatorError> {
+        self.commands.push(Command {
+            code,
+            data: Vec::new(),
+        });
+        match code {
+            POWER_ON | POWER_OFF => self.busy_polls = 1,
+            DATA_START_TRANSMISSION_2 => self.ram_cursor = 0,
+            DISPLAY_REFRESH => self.refresh()?,
+            PARTIAL_OUT => self.window = None,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Simulated panel, whose [`epd`](Simulator::epd) drives it.
+#[derive(Default)]
+pub struct Simulator {
+    panel: Rc<RefCell<Panel>>,
+}
+
+pub type SimulatedEpd = Epd4in2<Spi, Pin, Pin, BusyPin, Delay>;
+
+impl Simulator {
+    pub fn new() -> Self {
+        Simulator::default()
+    }
+
+    /// Simulated panel already displaying `frame`.
+    pub fn with_frame(frame: Frame) -> Self {
+        let simulator = Simulator::default();
+        simulator.panel.borrow_mut().frame = Some(frame);
+        simulator
+    }
+
+    /// Driver of the simulated panel.
+    pub fn epd(&self) -> SimulatedEpd {
+        Epd4in2::new(
+            Spi(self.panel.clone()),
+            Pin(self.panel.clone(), PinRole::DataCommand),
+            Pin(self.panel.clone(), PinRole::Reset),
+            BusyPin(self.panel.clone()),
+            Delay(self.panel.clone()),
+        )
+    }
+
+    /// Image displayed by the last refresh, if any.
+    pub fn frame(&self) -> Option<Frame> {
+        self.panel.borrow().frame.clone()
+    }
+
+    /// Commands received so far, in order.
+    pub fn commands(&self) -> Vec<Command> {
+        self.panel.borrow().commands.clone()
+    }
+
+    /// Full refreshes so far.
+    pub fn refreshes(&self) -> usize {
+        self.panel.borrow().refreshes
+    }
+
+    pub fn partial_refreshes(&self) -> usize {
+        self.panel.borrow().partial_refreshes
+    }
+
+    pub fn asleep(&self) -> bool {
+        self.panel.borrow().asleep
+    }
+
+    /// Time the driver waited, without actually sleeping.
+    pub fn delay_ms(&self) -> u64 {
+        self.panel.borrow().delay_ms
+    }
+}
+
+pub struct Spi(Rc<RefCell<Panel>>);
+
+impl Write<u8> for Spi {
+    type Error = SimulatorError;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        self.0.borrow_mut().receive(words)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum PinRole {
+    DataCommand,
+    Reset,
+}
+
+/// Data/command or reset pin of the simulated panel.
+pub struct Pin(Rc<RefCell<Panel>>, PinRole);
+
+impl OutputPin for Pin {
+    type Error = SimulatorError;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        let mut panel = self.0.borrow_mut();
+        match self.1 {
+            PinRole::DataCommand => panel.data_mode = false,
+            PinRole::Reset => panel.reset_high = false,
+        }
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let mut panel = self.0.borrow_mut();
+        match self.1 {
+            PinRole::DataCommand => panel.data_mode = true,
+            // a reset pulse wakes the panel from deep sleep
+            PinRole::Reset => {
+                if !panel.reset_high {
+                    panel.asleep = false;
+                }
+                panel.reset_high = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct BusyPin(Rc<RefCell<Panel>>);
+
+impl InputPin for BusyPin {
+    type Error = SimulatorError;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        let mut panel = self.0.borrow_mut();
+        let busy = panel.busy_polls > 0;
+        panel.busy_polls = panel.busy_polls.saturating_sub(1);
+        Ok(!busy)
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.is_high().map(|h| !h)
+    }
+}
+
+pub struct Delay(Rc<RefCell<Panel>>);
+
+impl DelayMs<u32> for Delay {
+    fn delay_ms(&mut self, ms: u32) {
+        self.0.borrow_mut().delay_ms += ms as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Layout;
+    use crate::render::{Renderer, DEFAULT_FONT_FILE};
+    use std::path::Path;
+
+    fn board() -> Frame {
+        let layout = Layout::default();
+        let renderer = Renderer::from_file(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_FONT_FILE),
+            layout.style.clone(),
+        )
+        .unwrap();
+        let lines = ["6\tGenève, Plage\t10:46\t1", "9\tVernier\t10:47\t0"]
+            .iter()
+            .map(|l| l.parse().unwrap())
+            .collect::<Vec<_>>();
+        layout.draw(&renderer, None, &lines)
+    }
+
+    #[test]
+    fn show_frame() {
+        let simulator = Simulator::new();
+        let frame = board();
+        simulator.epd().show(&frame).unwrap();
+        assert_eq!(simulator.frame(), Some(frame));
+        assert_eq!(simulator.refreshes(), 1);
+        assert!(simulator.asleep());
+        assert!(simulator.delay_ms() > 0);
+
+        let codes: Vec<u8> = simulator.commands().iter().map(|c| c.code).collect();
+        assert_eq!(codes[..3], [POWER_SETTING, BOOSTER_SOFT_START, POWER_ON]);
+        assert_eq!(codes[codes.len() - 2..], [GET_STATUS, DEEP_SLEEP]);
+        let resolution = simulator
+            .commands()
+            .into_iter()
+            .find(|c| c.code == RESOLUTION_SETTING)
+            .unwrap();
+        assert_eq!(resolution.data, [0x01, 0x90, 0x01, 0x2c]);
+    }
+
+    #[test]
+    fn wake_from_sleep() {
+        let simulator = Simulator::new();
+        let mut epd = simulator.epd();
+        epd.show(&board()).unwrap();
+        assert!(epd.display(&Frame::new(EPD_WIDTH, EPD_HEIGHT)).is_err());
+
+        let blank = Frame::new(EPD_WIDTH, EPD_HEIGHT);
+        epd.show(&blank).unwrap();
+        assert_eq!(simulator.frame(), Some(blank));
+        assert_eq!(simulator.refreshes(), 2);
+    }
+
+    #[test]
+    fn partial_refresh() {
+        let previous = board();
+        let mut frame = previous.clone();
+        frame.fill_rect((350, 130), (390, 170), true);
+        let regions = frame.changed_regions(&previous);
+        let simulator = Simulator::with_frame(previous.clone());
+        let mut epd = simulator.epd();
+        epd.refresh(&Refresh::Partial(regions), Some(&previous), &frame)
+            .unwrap();
+        assert_eq!(simulator.frame(), Some(frame.clone()));
+        assert_eq!(
+            (simulator.refreshes(), simulator.partial_refreshes()),
+            (0, 1)
+        );
+        let window = simulator
+            .commands()
+            .into_iter()
+            .find(|c| c.code == PARTIAL_WINDOW)
+            .unwrap();
+        // widened to whole bytes
+        assert_eq!(window.data, [0x01, 0x58, 0x01, 0x87, 0, 130, 0, 170, 0x28]);
+
+        // back to full refreshes after the partial ones
+        epd.refresh(&Refresh::Full, Some(&frame), &previous)
+            .unwrap();
+        assert_eq!(simulator.frame(), Some(previous.clone()));
+        assert_eq!(simulator.refreshes(), 1);
+
+        epd.refresh(&Refresh::Skip, Some(&previous), &frame)
+            .unwrap();
+        assert_eq!(simulator.frame(), Some(previous.clone()));
+        assert!(epd
+            .refresh(&Refresh::Partial(Vec::new()), None, &frame)
+            .is_err());
+        assert!(Simulator::new()
+            .epd()
+            .refresh(
+                &Refresh::Partial(frame.changed_regions(&previous)),
+                Some(&previous),
+                &frame
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn frame_size() {
+        let simulator = Simulator::new();
+        let mut epd = simulator.epd();
+        epd.init().unwrap();
+        assert!(epd.display(&Frame::new(EPD_HEIGHT, EPD_WIDTH)).is_err());
+        assert_eq!(simulator.refreshes(), 0);
+    }
+}
@@ -0,0 +1,26 @@
+//! Test helpers shared by the modules tests.
+
+use crate::model::StationBoardResponse;
+use chrono::{DateTime, Local};
+use std::{fs::File, io::BufReader, path::Path};
+
+pub const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%z";
+
+pub fn stationboard(file_name: &str) -> StationBoardResponse {
+    let file = File::open(Path::new(env!("CARGO_MANIFEST_DIR")).join(file_name)).unwrap();
+    serde_json::from_reader(BufReader::new(file)).unwrap()
+}
+
+pub fn stationboard_geneva() -> StationBoardResponse {
+    stationboard("geneve_cornavin_test.json")
+}
+
+pub fn stationboard_zurich() -> StationBoardResponse {
+    stationboard("zurich_hb_test.json")
+}
+
+pub fn local_time(s: &str) -> DateTime<Local> {
+    DateTime::parse_from_str(s, TIME_FORMAT)
+        .unwrap()
+        .with_timezone(&Local)
+}
@@ -0,0 +1,369 @@
+//! Display ready times, destinations and lines.
+
+use crate::badges::Badge;
+use crate::model::TransportMode;
+use chrono::{DateTime, Local};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Formats the hour and minute of `time` in `timezone`, independently of the
+/// system timezone.
+pub fn format_time(time: &DateTime<Local>, timezone: &Tz) -> String {
+    format!("{}", time.with_timezone(timezone).format("%H:%M"))
+}
+
+/// Parses a `;` separated list of `<word>=<abbreviation>` pairs.
+pub fn parse_abbreviations(s: &str) -> Result<Vec<(String, String)>, String> {
+    s.split(';')
+        .filter(|a| !a.trim().is_empty())
+        .map(|a| match a.split_once('=') {
+            Some((w, abbr)) if !w.trim().is_empty() => {
+                Ok((w.trim().to_owned(), abbr.trim().to_owned()))
+            }
+            _ => Err(format!("expected <word>=<abbreviation>, got \"{}\"", a)),
+        })
+        .collect()
+}
+
+/// Returns the city of a stop name, e.g. `Genève` for `Genève, gare Cornavin`.
+pub fn city(name: &str) -> Option<&str> {
+    name.split_once(", ").map(|(c, _)| c)
+}
+
+/// Replaces the whole words of `s` found in the abbreviations dictionary,
+/// ignoring the punctuation following them.
+pub fn abbreviate(s: &str, abbreviations: &[(String, String)]) -> String {
+    s.split(' ')
+        .map(|word| {
+            let bare = word.trim_end_matches([',', ';', ')']);
+            match abbreviations.iter().find(|(w, _)| w == bare) {
+                Some((_, abbr)) => format!("{}{}", abbr, &word[bare.len()..]),
+                None => word.to_owned(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Shortens `s` so that `width(s) <= max`, keeping as many leading words as
+/// possible and marking the cut with an ellipsis. A first word too long on its
+/// own is cut in the middle, and nothing is left when even the ellipsis is too
+/// wide.
+pub fn truncate_words(s: &str, max: usize, width: impl Fn(&str) -> usize) -> String {
+    if width(s) <= max {
+        return s.to_owned();
+    }
+    let words: Vec<&str> = s.split(' ').collect();
+    for nb_words in (1..words.len()).rev() {
+        let kept = words[..nb_words]
+            .join(" ")
+            .trim_end_matches([',', ';', '-', '/', ' '])
+            .to_owned()
+            + "…";
+        if width(&kept) <= max {
+            return kept;
+        }
+    }
+    let mut cut = String::new();
+    for c in s.chars() {
+        let next = format!("{}{}…", cut, c);
+        if width(&next) > max {
+            break;
+        }
+        cut.push(c);
+    }
+    let cut = cut + "…";
+    if width(&cut) <= max {
+        cut
+    } else {
+        String::new()
+    }
+}
+
+/// Display ready destination: the city is dropped when it is the one of the
+/// station, words are abbreviated, and the result is truncated to `max` as
+/// measured by `width` (characters, pixels, ...).
+pub fn short_destination(
+    to: &str,
+    station_name: &str,
+    abbreviations: &[(String, String)],
+    max: usize,
+    width: impl Fn(&str) -> usize,
+) -> String {
+    let to = match (city(to), city(station_name)) {
+        (Some(c), Some(station_city)) if c == station_city => &to[c.len() + 2..],
+        _ => to,
+    };
+    truncate_words(&abbreviate(to, abbreviations), max, width)
+}
+
+/// Event of a line drawn in the accent color of tri-color panels.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Alert {
+    /// Its delay.
+    Delay,
+    /// Its whole row, the departure having lost its realtime prognosis.
+    Cancellation,
+    /// Its destination, the platform being elsewhere than scheduled.
+    PlatformChange,
+}
+
+impl std::str::FromStr for Alert {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "delays" => Ok(Alert::Delay),
+            "cancellations" => Ok(Alert::Cancellation),
+            "platform_changes" => Ok(Alert::PlatformChange),
+            other => Err(format!(
+                "unknown alert \"{}\", expected delays, cancellations or platform_changes",
+                other
+            )),
+        }
+    }
+}
+
+/// Parses a `,` separated list of alerts, e.g. `delays,cancellations`.
+pub fn parse_alerts(s: &str) -> Result<Vec<Alert>, String> {
+    s.split(',')
+        .filter(|a| !a.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// A line of the board, displayed as tab separated values.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone)]
+pub struct LineInfo {
+    pub line_number: String,
+    pub direction: String,
+    pub normal_time: String,
+    pub delay: i32,
+    /// Mode whose icon is drawn before the line number, left out of the tab
+    /// separated values that the display controller reads.
+    #[serde(default)]
+    pub mode: Option<TransportMode>,
+    /// Badge the line number is drawn in, plain text when `None`, also left
+    /// out of the tab separated values.
+    #[serde(default)]
+    pub badge: Option<Badge>,
+    /// Events drawn in the accent color, also left out of the tab separated
+    /// values.
+    #[serde(default)]
+    pub alerts: Vec<Alert>,
+}
+
+impl fmt::Display for LineInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}",
+            self.line_number, self.direction, self.normal_time, self.delay
+        )
+    }
+}
+
+impl std::str::FromStr for LineInfo {
+    type Err = String;
+
+    /// Parses a line as displayed, e.g. from the result file.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let cols: Vec<&str> = s.split('\t').collect();
+        let [line_number, direction, normal_time, delay] = cols[..] else {
+            return Err(format!(
+                "expected 4 tab separated values, got {}",
+                cols.len()
+            ));
+        };
+        Ok(LineInfo {
+            line_number: line_number.to_owned(),
+            direction: direction.to_owned(),
+            normal_time: normal_time.to_owned(),
+            delay: delay
+                .trim()
+                .parse()
+                .map_err(|e| format!("invalid delay \"{}\": {}", delay, e))?,
+            mode: None,
+            badge: None,
+            alerts: Vec::new(),
+        })
+    }
+}
+
+/// Header of the board, above its lines.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Header {
+    /// Abbreviated name of the station.
+    pub station: String,
+    /// Time of the last successful update.
+    pub updated: String,
+    /// The lines are the ones of the last successful update, the following
+    /// ones having failed.
+    pub stale: bool,
+    /// None of the lines has realtime data.
+    pub timetable_only: bool,
+    /// Ids of the stations of a merged board which could not be fetched, their
+    /// lines missing from the board.
+    #[serde(default)]
+    pub failed: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DEFAULT_ABBREVIATIONS, DEFAULT_TIMEZONE};
+    use crate::fixtures::local_time;
+
+    fn default_abbreviations() -> Vec<(String, String)> {
+        parse_abbreviations(DEFAULT_ABBREVIATIONS).unwrap()
+    }
+
+    fn nb_chars(s: &str) -> usize {
+        s.chars().count()
+    }
+
+    #[test]
+    fn parse_abbreviations_list() {
+        assert_eq!(
+            parse_abbreviations("Bahnhof=Bhf; gare = g.;").unwrap(),
+            vec![
+                (String::from("Bahnhof"), String::from("Bhf")),
+                (String::from("gare"), String::from("g.")),
+            ]
+        );
+        assert!(parse_abbreviations("Bahnhof").is_err());
+        assert!(parse_abbreviations("=Bhf").is_err());
+    }
+
+    #[test]
+    fn abbreviate_whole_words() {
+        let a = default_abbreviations();
+        assert_eq!(abbreviate("Bern, Bahnhof", &a), "Bern, Bhf");
+        assert_eq!(abbreviate("Lausanne, gare", &a), "Lausanne, g.");
+        assert_eq!(abbreviate("Zürich Flughafen", &a), "Zürich Flugh.");
+        assert_eq!(abbreviate("Bahnhofstrasse", &a), "Bahnhofstrasse");
+    }
+
+    #[test]
+    fn truncate_on_words() {
+        assert_eq!(truncate_words("Plage", 8, nb_chars), "Plage");
+        assert_eq!(truncate_words("Vernier, village", 8, nb_chars), "Vernier…");
+        assert_eq!(truncate_words("Onex, cité", 8, nb_chars), "Onex…");
+        assert_eq!(truncate_words("Grand-Saconnex", 8, nb_chars), "Grand-S…");
+        assert_eq!(truncate_words("Bern / Zürich HB", 8, nb_chars), "Bern…");
+        assert_eq!(truncate_words("Plage", 1, nb_chars), "…");
+        assert_eq!(truncate_words("Plage", 0, nb_chars), "");
+        assert_eq!(
+            truncate_words("Thônex, Belle-Terre Pl. Araire", 20, nb_chars),
+            "Thônex, Belle-Terre…"
+        );
+        // pixel widths, e.g. 10px per character plus a 3px ellipsis
+        let px = |s: &str| s.chars().map(|c| if c == '…' { 3 } else { 10 }).sum();
+        assert_eq!(truncate_words("Vernier, village", 75, px), "Vernier…");
+        assert_eq!(truncate_words("Vernier, village", 2, px), "");
+    }
+
+    #[test]
+    fn short_destinations() {
+        let a = default_abbreviations();
+        let station = "Genève, gare Cornavin";
+        assert_eq!(
+            short_destination("Genève, Plage", station, &a, 8, nb_chars),
+            "Plage"
+        );
+        assert_eq!(
+            short_destination("Genève, Jardin Botanique", station, &a, 8, nb_chars),
+            "Jardin…"
+        );
+        assert_eq!(
+            short_destination("Genève-Aéroport, Terminal", station, &a, 30, nb_chars),
+            "Genève-Aéroport, Terminal"
+        );
+        assert_eq!(
+            short_destination("Vernier, village", station, &a, 30, nb_chars),
+            "Vernier, village"
+        );
+        assert_eq!(
+            short_destination("Bern, Bahnhof", "Zürich HB", &a, 9, nb_chars),
+            "Bern, Bhf"
+        );
+        assert_eq!(
+            short_destination("Chur", "Zürich HB", &a, 8, nb_chars),
+            "Chur"
+        );
+    }
+
+    #[test]
+    fn format_time_in_timezone() {
+        let t = local_time("2024-04-19T12:09:00+0200");
+        assert_eq!(format_time(&t, &DEFAULT_TIMEZONE), "12:09");
+        assert_eq!(format_time(&t, &chrono_tz::UTC), "10:09");
+        assert_eq!(format_time(&t, &chrono_tz::Europe::London), "11:09");
+    }
+
+    #[test]
+    fn format_time_across_dst() {
+        // 2024-03-31 02:00 CET jumps to 03:00 CEST
+        assert_eq!(
+            format_time(&local_time("2024-03-31T00:59:00+0000"), &DEFAULT_TIMEZONE),
+            "01:59"
+        );
+        assert_eq!(
+            format_time(&local_time("2024-03-31T01:00:00+0000"), &DEFAULT_TIMEZONE),
+            "03:00"
+        );
+        // 2024-10-27 03:00 CEST goes back to 02:00 CET, 02:30 happens twice
+        assert_eq!(
+            format_time(&local_time("2024-10-27T00:30:00+0000"), &DEFAULT_TIMEZONE),
+            "02:30"
+        );
+        assert_eq!(
+            format_time(&local_time("2024-10-27T01:30:00+0000"), &DEFAULT_TIMEZONE),
+            "02:30"
+        );
+    }
+
+    #[test]
+    fn format_time_across_midnight() {
+        // still the 19th in UTC but already the 20th in Zurich
+        let t = local_time("2024-04-19T22:05:00+0000");
+        assert_eq!(format_time(&t, &DEFAULT_TIMEZONE), "00:05");
+        assert_eq!(format_time(&t, &chrono_tz::UTC), "22:05");
+        let t = local_time("2024-12-31T23:30:00+0000");
+        assert_eq!(format_time(&t, &DEFAULT_TIMEZONE), "00:30");
+    }
+
+    #[test]
+    fn parse_line_info() {
+        let l = LineInfo {
+            line_number: String::from("6"),
+            direction: String::from("Genève, Plage"),
+            normal_time: String::from("10:46"),
+            delay: 1,
+            mode: None,
+            badge: None,
+            alerts: Vec::new(),
+        };
+        assert_eq!(l.to_string().parse(), Ok(l.clone()));
+        // the mode and alerts are not part of the displayed values
+        let tram = LineInfo {
+            mode: Some(TransportMode::Tram),
+            alerts: vec![Alert::Delay],
+            ..l.clone()
+        };
+        assert_eq!(tram.to_string(), l.to_string());
+        assert!("6\tGenève, Plage\t10:46".parse::<LineInfo>().is_err());
+        assert!("6\tGenève, Plage\t10:46\t+".parse::<LineInfo>().is_err());
+    }
+
+    #[test]
+    fn parse_alerts_list() {
+        assert_eq!(
+            parse_alerts("delays, Platform_Changes,").unwrap(),
+            vec![Alert::Delay, Alert::PlatformChange]
+        );
+        assert!(parse_alerts("delays,red").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! Health status of the updates, written to a file after each of them for
+//! watchdogs and monitoring to check, and the JSON files kept between updates.
+
+use chrono::{DateTime, Local};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Outcome of the updates so far.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct Health {
+    pub last_attempt: Option<DateTime<Local>>,
+    pub last_success: Option<DateTime<Local>>,
+    pub consecutive_failures: u32,
+    /// Error of the last update, `None` when it succeeded.
+    pub error: Option<String>,
+}
+
+impl Health {
+    /// Reads the health file at `path`, no update having been made yet when
+    /// missing.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        read_json(path)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        write_json(path, self)
+    }
+
+    /// Health after an update attempted at `now` with `result`.
+    pub fn record(self, now: DateTime<Local>, result: &Result<(), String>) -> Self {
+        match result {
+            Ok(()) => Health {
+                last_attempt: Some(now),
+                last_success: Some(now),
+                consecutive_failures: 0,
+                error: None,
+            },
+            Err(e) => Health {
+                last_attempt: Some(now),
+                consecutive_failures: self.consecutive_failures + 1,
+                error: Some(e.clone()),
+                ..self
+            },
+        }
+    }
+
+    /// Fails when the last success is older than `max_age` at `now`, or when
+    /// there was none.
+    pub fn check(&self, now: DateTime<Local>, max_age: chrono::Duration) -> Result<(), String> {
+        let failures = match &self.error {
+            Some(e) => format!(
+                ", {} consecutive failures, last one: {}",
+                self.consecutive_failures, e
+            ),
+            None => String::new(),
+        };
+        match self.last_success {
+            None => Err(format!("no successful update{}", failures)),
+            Some(s) if now - s > max_age => Err(format!(
+                "last successful update {} minutes ago{}",
+                (now - s).num_minutes(),
+                failures
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+/// Reads the JSON file at `path`, a missing file giving the default value.
+pub fn read_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T, String> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => serde_json::from_str(&s)
+            .map_err(|e| format!("could not parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(format!("could not read {}: {}", path.display(), e)),
+    }
+}
+
+/// Writes `value` as JSON to the file at `path`, replacing it at once so that
+/// readers never see it half written.
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("could not serialize {}: {}", path.display(), e))?;
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, json).map_err(|e| format!("could not write {}: {}", tmp.display(), e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("could not write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::local_time;
+
+    #[test]
+    fn record_updates() {
+        let t0 = local_time("2024-04-19T12:00:00+0200");
+        let t1 = local_time("2024-04-19T12:01:00+0200");
+        let t2 = local_time("2024-04-19T12:02:00+0200");
+        let h = Health::default().record(t0, &Ok(()));
+        let h = h.record(t1, &Err(String::from("rate limited")));
+        let h = h.record(t2, &Err(String::from("timed out")));
+        assert_eq!(
+            h,
+            Health {
+                last_attempt: Some(t2),
+                last_success: Some(t0),
+                consecutive_failures: 2,
+                error: Some(String::from("timed out")),
+            }
+        );
+        assert_eq!(h.record(t2, &Ok(())).consecutive_failures, 0);
+    }
+
+    #[test]
+    fn check_age() {
+        let t0 = local_time("2024-04-19T12:00:00+0200");
+        let max_age = chrono::Duration::minutes(10);
+        assert!(Health::default().check(t0, max_age).is_err());
+
+        let h = Health::default()
+            .record(t0, &Ok(()))
+            .record(t0, &Err(String::from("rate limited")));
+        assert!(h.check(t0 + chrono::Duration::minutes(10), max_age).is_ok());
+        let e = h
+            .check(t0 + chrono::Duration::minutes(11), max_age)
+            .unwrap_err();
+        assert!(e.contains("11 minutes ago"), "{}", e);
+        assert!(e.contains("rate limited"), "{}", e);
+    }
+
+    #[test]
+    fn write_read() {
+        let path = std::env::temp_dir().join(format!("health_{}.json", std::process::id()));
+        assert_eq!(Health::read(&path), Ok(Health::default()));
+        let h = Health::default().record(
+            local_time("2024-04-19T12:00:00+0200"),
+            &Err(String::from("rate limited")),
+        );
+        h.write(&path).unwrap();
+        assert_eq!(Health::read(&path), Ok(h));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+//! Transport mode icons drawn before the line numbers, bundled as 48x48
+//! bitmaps of white pictograms on black.
+
+use crate::model::{Journey, TransportMode};
+use crate::render::Frame;
+use crate::selection::LineSelector;
+use image::imageops::FilterType;
+use std::str::FromStr;
+
+/// Bundled png bitmap of the icon of `mode`.
+fn bitmap(mode: TransportMode) -> &'static [u8] {
+    match mode {
+        TransportMode::Train => include_bytes!("../icons/train.png"),
+        TransportMode::Tram => include_bytes!("../icons/tram.png"),
+        TransportMode::Bus => include_bytes!("../icons/bus.png"),
+        TransportMode::Ship => include_bytes!("../icons/ship.png"),
+        TransportMode::Cableway => include_bytes!("../icons/cableway.png"),
+        TransportMode::Metro => include_bytes!("../icons/metro.png"),
+    }
+}
+
+/// Icon of `mode`, scaled to `size` pixels wide and high.
+pub fn icon(mode: TransportMode, size: u32) -> Frame {
+    let image = image::load_from_memory_with_format(bitmap(mode), image::ImageFormat::Png)
+        .expect("bundled icons should be valid png")
+        .to_luma8();
+    Frame::from_image(&image::imageops::resize(
+        &image,
+        size,
+        size,
+        FilterType::Triangle,
+    ))
+}
+
+/// Mode whose icon is drawn for `journey`, the one of the first matching line
+/// of `overrides` or else its own.
+pub fn journey_icon(
+    journey: &Journey,
+    overrides: &[(LineSelector, TransportMode)],
+) -> Option<TransportMode> {
+    overrides
+        .iter()
+        .find(|(l, _)| l.matches(journey))
+        .map(|(_, m)| *m)
+        .or_else(|| journey.transport_mode())
+}
+
+/// Parses a `;` separated list of `<line>=<mode>` icon overrides, the lines
+/// as in [`LineSelector`], e.g. `12=tram;E:Bel-Air=bus`.
+pub fn parse_line_icons(s: &str) -> Result<Vec<(LineSelector, TransportMode)>, String> {
+    s.split(';')
+        .filter(|o| !o.trim().is_empty())
+        .map(|o| match o.rsplit_once('=') {
+            Some((l, m)) => Ok((LineSelector::from_str(l)?, TransportMode::from_str(m)?)),
+            None => Err(format!("expected <line>=<mode>, got \"{}\"", o)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::stationboard_geneva;
+
+    #[test]
+    fn scaled_icons() {
+        for mode in [
+            TransportMode::Train,
+            TransportMode::Tram,
+            TransportMode::Bus,
+            TransportMode::Ship,
+            TransportMode::Cableway,
+            TransportMode::Metro,
+        ] {
+            let icon = icon(mode, 24);
+            assert_eq!((icon.width, icon.height), (24, 24));
+            // white pictograms on black, the corners being rounded
+            assert!(icon.get(12, 1));
+            assert!(!icon.get(0, 0));
+            assert!((0..24).any(|y| (0..24).any(|x| !icon.get(x, y) && x > 2 && y > 2)));
+        }
+        assert_ne!(icon(TransportMode::Tram, 24), icon(TransportMode::Bus, 24));
+    }
+
+    #[test]
+    fn overridden_icons() {
+        let board = stationboard_geneva();
+        // the bus 3 and the tram 15
+        let bus = &board.stationboard[0].journey;
+        let tram = &board.stationboard[7].journey;
+        assert_eq!(journey_icon(bus, &[]), Some(TransportMode::Bus));
+        assert_eq!(journey_icon(tram, &[]), Some(TransportMode::Tram));
+
+        let overrides = parse_line_icons("3:Grand-Saconnex=tram; 15 = metro;").unwrap();
+        assert_eq!(journey_icon(bus, &overrides), Some(TransportMode::Tram));
+        assert_eq!(journey_icon(tram, &overrides), Some(TransportMode::Metro));
+        assert_eq!(
+            journey_icon(bus, &parse_line_icons("3:Onex=tram").unwrap()),
+            Some(TransportMode::Bus)
+        );
+
+        for invalid in ["3", "3=hovercraft", "=tram", "3:=bus"] {
+            assert!(parse_line_icons(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
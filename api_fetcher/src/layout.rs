@@ -0,0 +1,270 @@
+//! Layouts of the board on the different panels, described in TOML.
+
+use crate::format::{Header, LineInfo};
+use crate::render::{Frame, Planes, Renderer, Style};
+use serde::Deserialize;
+
+pub const DEFAULT_LAYOUT: &str = "4in2";
+/// Built-in layouts by name, one per supported Waveshare panel.
+pub const PRESETS: [(&str, &str); 7] = [
+    ("2in13", include_str!("../layouts/2in13.toml")),
+    ("2in9", include_str!("../layouts/2in9.toml")),
+    ("4in2", include_str!("../layouts/4in2.toml")),
+    ("4in2b", include_str!("../layouts/4in2b.toml")),
+    ("5in83", include_str!("../layouts/5in83.toml")),
+    ("7in5", include_str!("../layouts/7in5.toml")),
+    ("7in5bc", include_str!("../layouts/7in5bc.toml")),
+];
+
+/// Geometry of the board on a panel.
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Layout {
+    /// Resolution of the panel, as its driver expects the frames.
+    pub width: u32,
+    pub height: u32,
+    /// Clockwise rotation of the board on the panel, in degrees.
+    #[serde(default)]
+    pub rotation: u32,
+    /// Number of lines displayed.
+    pub rows: usize,
+    /// Space kept above the rows, for the header when not 0.
+    #[serde(default)]
+    pub header_height: u32,
+    /// Space kept below the rows.
+    #[serde(default)]
+    pub footer_height: u32,
+    /// The panel also has an accent color, e.g. red, the board being drawn in
+    /// two planes.
+    #[serde(default)]
+    pub tri_color: bool,
+    #[serde(default)]
+    pub style: Style,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::preset(DEFAULT_LAYOUT).expect("the default layout should be a preset")
+    }
+}
+
+impl Layout {
+    /// Parses and checks a TOML layout.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let layout: Layout = toml::from_str(s).map_err(|e| format!("{}", e))?;
+        layout.check()?;
+        Ok(layout)
+    }
+
+    /// The built-in layout `name`, see [`PRESETS`].
+    pub fn preset(name: &str) -> Option<Self> {
+        PRESETS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, l)| Layout::parse(l).expect("presets should be valid"))
+    }
+
+    /// The preset named `name_or_path`, or else the layout file at this path.
+    pub fn load(name_or_path: &str) -> Result<Self, String> {
+        if let Some(l) = Layout::preset(name_or_path) {
+            return Ok(l);
+        }
+        let s = std::fs::read_to_string(name_or_path).map_err(|e| {
+            format!(
+                "not a preset among {} nor a readable file: {}",
+                PRESETS.map(|(n, _)| n).join(", "),
+                e
+            )
+        })?;
+        Layout::parse(&s)
+    }
+
+    /// Size of the board before its rotation onto the panel.
+    pub fn canvas_size(&self) -> (u32, u32) {
+        match self.rotation {
+            90 | 270 => (self.height, self.width),
+            _ => (self.width, self.height),
+        }
+    }
+
+    /// Panel frame of the board lines, at most `rows` of them, below the
+    /// header if the layout has room for one.
+    pub fn draw(&self, renderer: &Renderer, header: Option<&Header>, lines: &[LineInfo]) -> Frame {
+        self.draw_planes(renderer, header, lines).merged()
+    }
+
+    /// Panel planes of the board, as [`Layout::draw`] but with the alerts of
+    /// the lines on the accent plane.
+    pub fn draw_planes(
+        &self,
+        renderer: &Renderer,
+        header: Option<&Header>,
+        lines: &[LineInfo],
+    ) -> Planes {
+        let (width, height) = self.canvas_size();
+        let mut canvas = Planes::new(width, height);
+        if let Some(h) = header.filter(|_| self.header_height > 0) {
+            renderer.draw_header(&mut canvas.black, self.header_height, h);
+        }
+        renderer.draw_rows(
+            &mut canvas,
+            self.header_height,
+            &lines[..lines.len().min(self.rows)],
+        );
+        canvas.rotate(self.rotation / 90)
+    }
+
+    /// Checks that the rotation is a multiple of 90° and that the rows fit.
+    pub fn check(&self) -> Result<(), String> {
+        if !self.rotation.is_multiple_of(90) || self.rotation >= 360 {
+            return Err(format!(
+                "rotation must be 0, 90, 180 or 270, not {}",
+                self.rotation
+            ));
+        }
+        // the separator of the last row may fall off the panel, not its text
+        let (_, height) = self.canvas_size();
+        let rows_bottom = self.header_height as f32
+            + self.rows.saturating_sub(1) as f32 * self.style.row_height as f32
+            + self.style.font_size;
+        if rows_bottom > height.saturating_sub(self.footer_height) as f32 {
+            return Err(format!(
+                "{} rows of {}px do not fit between the header and the footer",
+                self.rows, self.style.row_height
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Alert;
+    use crate::render::DEFAULT_FONT_FILE;
+    use std::path::Path;
+
+    fn lines(n: usize) -> Vec<LineInfo> {
+        (0..n)
+            .map(|i| LineInfo {
+                line_number: format!("{}", i + 1),
+                direction: String::from("Genève-Aéroport"),
+                normal_time: String::from("10:46"),
+                delay: i as i32,
+                mode: None,
+                badge: None,
+                alerts: Vec::new(),
+            })
+            .collect()
+    }
+
+    fn header() -> Header {
+        Header {
+            station: String::from("Genève, g. Cornavin"),
+            updated: String::from("12:05"),
+            stale: false,
+            timetable_only: false,
+            failed: Vec::new(),
+        }
+    }
+
+    fn renderer(layout: &Layout) -> Renderer {
+        Renderer::from_file(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_FONT_FILE),
+            layout.style.clone(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn presets() {
+        assert_eq!(Layout::default().style, Style::default());
+        for (name, _) in PRESETS {
+            let layout = Layout::preset(name).unwrap();
+            let frame = layout.draw(&renderer(&layout), Some(&header()), &lines(10));
+            assert_eq!((frame.width, frame.height), (layout.width, layout.height));
+        }
+        assert_eq!(Layout::preset("13in3"), None);
+    }
+
+    #[test]
+    fn rotated_layout() {
+        let layout = Layout::preset("2in9").unwrap();
+        assert_eq!(layout.canvas_size(), (296, 128));
+        let r = renderer(&layout);
+        let frame = layout.draw(&r, None, &lines(1));
+        let mut canvas = Planes::new(296, 128);
+        r.draw_rows(&mut canvas, 0, &lines(1));
+        assert_eq!(frame, canvas.merged().rotate(1));
+    }
+
+    #[test]
+    fn tri_color_presets() {
+        let black_white = Layout::preset("4in2").unwrap();
+        let layout = Layout::preset("4in2b").unwrap();
+        assert!(layout.tri_color && !black_white.tri_color);
+        assert_eq!(
+            Layout {
+                tri_color: false,
+                ..layout.clone()
+            },
+            black_white
+        );
+
+        let r = renderer(&layout);
+        let mut lines = lines(2);
+        lines[1].alerts = vec![Alert::Delay];
+        let planes = layout.draw_planes(&r, Some(&header()), &lines);
+        assert_eq!(planes.merged(), layout.draw(&r, Some(&header()), &lines));
+        // only the delay of the second row is on the accent plane
+        let accent_rows: Vec<i32> = (0..300)
+            .filter(|&y| (0..400).any(|x| planes.accent.get(x, y)))
+            .collect();
+        assert!(!accent_rows.is_empty());
+        assert!(accent_rows.iter().all(|y| (65..2 * 65).contains(y)));
+        assert!(Layout::preset("7in5bc").unwrap().tri_color);
+    }
+
+    #[test]
+    fn header_and_rows() {
+        let mut layout = Layout::parse(
+            r#"
+            width = 400
+            height = 300
+            rows = 2
+            header_height = 40
+            "#,
+        )
+        .unwrap();
+        let r = renderer(&layout);
+        let frame = layout.draw(&r, None, &lines(5));
+        let black_rows: Vec<i32> = (0..300)
+            .filter(|&y| (0..400).any(|x| frame.get(x, y)))
+            .collect();
+        assert!(black_rows[0] >= 40);
+        assert!(*black_rows.last().unwrap() < 40 + 2 * 65);
+
+        // the header is drawn above the rows, which stay the same
+        let with_header = layout.draw(&r, Some(&header()), &lines(5));
+        assert!((0..40).any(|y| (0..400).any(|x| with_header.get(x, y))));
+        assert!((40..300).all(|y| (0..400).all(|x| with_header.get(x, y) == frame.get(x, y))));
+
+        layout.rows = 1;
+        assert_ne!(layout.draw(&r, None, &lines(5)), frame);
+    }
+
+    #[test]
+    fn invalid_layouts() {
+        for l in [
+            "width = 400\nheight = 300\nrows = 5\nrotation = 45",
+            "width = 400\nheight = 300\nrows = 6",
+            "width = 400\nheight = 300\nrows = 5\nheader_height = 40",
+            "width = 400\nheight = 300",
+            "width = 400\nheight = 300\nrows = 5\ncolumns = 4",
+            "width = 400\nheight = 300\nrows = 5\n[style]\nfont = \"Menlo\"",
+        ] {
+            assert!(Layout::parse(l).is_err(), "{}", l);
+        }
+        assert!(Layout::load("13in3").is_err());
+    }
+}
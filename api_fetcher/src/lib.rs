@@ -0,0 +1,56 @@
+//! Fetches the next departures of a station from the swiss public transport
+//! API (<https://transport.opendata.ch>) and prepares them for a small display.
+//!
+//! With the `blocking` feature:
+//!
+//! ```no_run
+//! use api_fetcher::{board, config::Config};
+//!
+//! # #[cfg(feature = "blocking")] {
+//! let config = Config::from_env()?;
+//! let merged = board::fetch(&config, None)?;
+//! for l in board::lines_info(&merged.board, &config) {
+//!     println!("{}", l);
+//! }
+//! # }
+//! # Ok::<(), String>(())
+//! ```
+//!
+//! - [`model`]: types of the API responses.
+//! - [`client`]: requests to the stationboard endpoint, blocking with the
+//!   `blocking` feature (default), async with the `async` one.
+//! - [`selection`]: which departures get a slot on the board.
+//! - [`format`](mod@format): display ready times and destinations.
+//! - [`datetime`]: datetime the board is shown at.
+//! - [`config`]: configuration read from the environment.
+//! - [`board`]: the above put together.
+//! - [`render`]: the board drawn into a 1-bit frame.
+//! - [`icons`]: transport mode icons before the line numbers.
+//! - [`badges`]: badges around the line numbers.
+//! - [`layout`]: geometry of the board on the different panels.
+//! - `epd`: driver of the 4.2" e-paper panel and its simulator, with the `epd`
+//!   feature.
+//! - [`displayed`]: board last displayed, not to display it again unchanged.
+//! - [`health`]: status of the updates for watchdogs.
+//! - `metrics`: prometheus metrics of the fetches, with the `metrics` feature.
+
+pub mod badges;
+pub mod board;
+pub mod client;
+pub mod config;
+pub mod datetime;
+pub mod displayed;
+#[cfg(feature = "epd")]
+pub mod epd;
+pub mod format;
+pub mod health;
+pub mod icons;
+pub mod layout;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod model;
+pub mod render;
+pub mod selection;
+
+#[cfg(test)]
+mod fixtures;
@@ -1,791 +1,428 @@
-use chrono::{DateTime, Local};
+use api_fetcher::board;
+#[cfg(feature = "epd")]
+use api_fetcher::config::EpdBackend;
+use api_fetcher::config::{Config, LogFormat};
+use api_fetcher::datetime::{board_datetime, parse_board_datetime, BoardDateTime};
+use api_fetcher::displayed::{board_hash, Displayed};
+#[cfg(feature = "epd")]
+use api_fetcher::epd::refresh::{displayed_frame_file, Refresh, RefreshPolicy, RefreshState};
+#[cfg(feature = "epd")]
+use api_fetcher::epd::simulator::Simulator;
+use api_fetcher::format::{format_time, Header, LineInfo};
+use api_fetcher::health::Health;
+#[cfg(feature = "epd")]
+use api_fetcher::render::Frame;
+use api_fetcher::render::{accent_plane_file, Renderer};
+use chrono::Local;
 use dotenv::dotenv;
-use reqwest::blocking::get;
-use reqwest::Url;
-use serde::{Deserialize, Serialize};
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::{prelude::*, IsTerminal};
 use std::path::Path;
-use std::{cmp::min, fmt};
+use tracing_subscriber::EnvFilter;
 
-const STATIONBOARD_ENDPOINT: &str = "https://transport.opendata.ch/v1/stationboard";
-const JOURNEYS_LIMIT: u32 = 5;
-const MAX_DISPLAYED_LINES: usize = 5;
 const RESULT_FILE_NAME: &str = "api_result.tsv";
+// exit code of a single update leaving the board displayed as it is
+const UNCHANGED_EXIT_CODE: i32 = 3;
 
 fn main() {
     dotenv().ok();
-    let station_id = std::env::var("STATION_ID").expect("STATION_ID must be set in .env file.");
-    let station_board = StationBoardResponse::get(
-        None,
-        Some(&station_id),
-        Some(JOURNEYS_LIMIT),
-        None,
-        None,
-        None,
-    )
-    .expect("error with the API call");
-    // println!("{:#?}", station_board);
-
-    let nb_stations = min(station_board.stationboard.len(), MAX_DISPLAYED_LINES);
-    let mut lines_info: Vec<LineInfo> = Vec::with_capacity(nb_stations);
-    for i in 0..nb_stations {
-        let s = &station_board.stationboard[i].stop;
-        let j = &station_board.stationboard[i].journey;
-        lines_info.push(LineInfo {
-            line_number: j
-                .number
-                .to_owned()
-                .expect("expect line number to be available"),
-            direction: j.to.to_owned(),
-            normal_departure: format!(
-                "{}",
-                s.departure
-                    .expect("departure time should be present")
-                    .format("%H:%M")
-            ),
-            delay: s.delay.unwrap_or_default(),
-        })
+    let args = parse_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!(
+            "{}\nusage: api_fetcher [--at \"YYYY-MM-DD HH:MM\" | --at HH:MM]\n       \
+             api_fetcher health [--max-age MINUTES]",
+            e
+        );
+        std::process::exit(2)
+    });
+    let config = Config::from_env().unwrap_or_else(|e| {
+        eprintln!("{}, see .env.example", e);
+        std::process::exit(2)
+    });
+    if args.health {
+        let max_age = args.max_age.unwrap_or(config.health_max_age);
+        let health = Health::read(&config.health_file).and_then(|h| h.check(Local::now(), max_age));
+        match health {
+            Ok(()) => println!("healthy"),
+            Err(e) => {
+                println!("unhealthy: {}", e);
+                std::process::exit(1)
+            }
+        }
+        return;
     }
-
-    let mut to_write = String::from("");
-    for l in lines_info {
-        println!("{}", l);
-        to_write += &l.to_string();
-        to_write.push('\n');
+    init_logs(&config).unwrap_or_else(|e| {
+        eprintln!("{}, see .env.example", e);
+        std::process::exit(2)
+    });
+
+    #[cfg(feature = "metrics")]
+    if let Some(a) = config.metrics_address {
+        api_fetcher::metrics::serve(a).unwrap_or_else(|e| {
+            tracing::error!(error = e, "could not serve the metrics");
+            std::process::exit(2)
+        });
+    }
+    #[cfg(not(feature = "metrics"))]
+    if config.metrics_address.is_some() {
+        tracing::error!("METRICS_ADDRESS requires building with the metrics feature");
+        std::process::exit(2)
+    }
+    #[cfg(not(feature = "epd"))]
+    if config.epd.is_some() {
+        tracing::error!("EPD requires building with the epd feature");
+        std::process::exit(2)
+    }
+    #[cfg(all(feature = "epd", not(all(feature = "epd-linux", target_os = "linux"))))]
+    if config.epd == Some(EpdBackend::Linux) {
+        tracing::error!("EPD=linux requires building on Linux with the epd-linux feature");
+        std::process::exit(2)
     }
 
-    let path = Path::new(RESULT_FILE_NAME);
-    let display = path.display();
-
-    // Open a file in write-only mode, returns `io::Result<File>`
-    let mut file = match File::create(&path) {
-        Err(why) => panic!("couldn't create {}: {}", display, why),
-        Ok(file) => file,
+    let Some(interval) = config.update_interval else {
+        match update_recording_health(&config, args.at) {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(UNCHANGED_EXIT_CODE),
+            Err(_) => std::process::exit(1),
+        }
     };
-
-    // Write the `to_write` string to `file`, returns `io::Result<()>`
-    match file.write_all(to_write.as_bytes()) {
-        Err(why) => panic!("couldn't write to {}: {}", display, why),
-        Ok(_) => println!("successfully wrote to {}", display),
+    loop {
+        // a failed update is retried at the next interval
+        let _ = update_recording_health(&config, args.at);
+        std::thread::sleep(interval);
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
-struct Coordinate {
-    r#type: String,
-    x: Option<f32>,
-    y: Option<f32>,
-}
-
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
-struct Location {
-    id: Option<String>,
-    name: Option<String>,
-    score: Option<f32>,
-    coordinate: Coordinate,
-    distance: Option<f32>,
-}
-
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
-struct Prognosis {
-    platform: Option<String>,
-    departure: Option<DateTime<Local>>,
-    arrival: Option<DateTime<Local>>,
-    capacity1st: Option<u32>,
-    capacity2nd: Option<u32>,
-}
-
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
-struct Stop {
-    station: Location,
-    arrival: Option<DateTime<Local>>,
-    departure: Option<DateTime<Local>>,
-    delay: Option<i32>,
-    platform: Option<String>,
-    prognosis: Option<Prognosis>,
-}
-
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
-#[serde(rename_all = "camelCase")]
-struct Journey {
-    name: Option<String>,
-    category: String,
-    category_code: Option<String>,
-    number: Option<String>,
-    operator: String,
-    to: String,
-    capacity1st: Option<u32>,
-    capacity2nd: Option<u32>,
-    pass_list: Vec<Stop>,
-}
-
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
-struct StationBoardElement {
-    stop: Stop,
-    #[serde(flatten)]
-    journey: Journey,
-}
-
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
-struct StationBoardResponse {
-    station: Location,
-    stationboard: Vec<StationBoardElement>,
+/// Updates the board, logging its errors and recording its outcome in the
+/// health file.
+fn update_recording_health(config: &Config, at: Option<BoardDateTime>) -> Result<bool, String> {
+    let result = update(config, at);
+    if let Err(e) = &result {
+        tracing::error!(error = e, "could not update the board");
+    }
+    let health = Health::read(&config.health_file)
+        .unwrap_or_else(|e| {
+            tracing::warn!(
+                error = e,
+                "could not read the previous health, resetting it"
+            );
+            Health::default()
+        })
+        .record(Local::now(), &result.clone().map(|_| ()));
+    if let Err(e) = health.write(&config.health_file) {
+        tracing::warn!(error = e, "could not write the health file");
+    }
+    result
 }
 
-impl StationBoardResponse {
-    fn get(
-        station: Option<&str>,
-        id: Option<&str>,
-        limit: Option<u32>,
-        transportations: Option<Vec<&str>>,
-        datetime: Option<DateTime<Local>>,
-        r#type: Option<&str>,
-    ) -> Result<Self, String> {
-        if station == None && id == None {
-            return Err(String::from("must provide either a station or an id"));
-        }
-        let mut args: Vec<(&str, String)> = Vec::with_capacity(6);
-        match station {
-            Some(s) => args.push(("station", s.to_owned())),
-            _ => (),
-        }
-        match id {
-            Some(s) => args.push(("id", s.to_owned())),
-            _ => (),
-        }
-        match r#type {
-            Some(s) => args.push(("type", s.to_owned())),
-            _ => (),
-        }
-        match limit {
-            Some(l) => args.push(("limit", l.to_string())),
-            _ => (),
-        }
-        match transportations {
-            Some(t) => {
-                for e in t {
-                    args.push(("transportations", e.to_owned()))
-                }
+/// Fetches the board and writes its lines to `RESULT_FILE_NAME`, then displays
+/// it unless unchanged, returning whether it did. When the fetch fails, the
+/// board is displayed again marked stale.
+fn update(config: &Config, at: Option<BoardDateTime>) -> Result<bool, String> {
+    let datetime = board_datetime(
+        Local::now().with_timezone(&config.timezone),
+        at.or(config.board_datetime),
+        config.morning_preview,
+    );
+    if let Some(d) = datetime {
+        tracing::info!(
+            datetime = %d.format("%Y-%m-%d %H:%M"),
+            "showing the board at a given datetime"
+        );
+    }
+    let result = board::fetch(config, datetime.map(|d| d.with_timezone(&Local)));
+    #[cfg(feature = "metrics")]
+    api_fetcher::metrics::observe_fetch(&config.station_ids, &result);
+    let merged = match result {
+        Ok(m) => m,
+        Err(e) => {
+            if let Err(e) = display_stale(config) {
+                tracing::warn!(error = e, "could not mark the board stale");
             }
-            _ => (),
-        }
-        match datetime {
-            Some(d) => args.push(("datetime", format!("{}", d.format("%Y-%m-%d %H:%M")))),
-            _ => (),
+            return Err(format!("could not fetch the board: {}", e));
         }
+    };
+    let lines_info = board::lines_info(&merged.board, config);
 
-        let url = Url::parse_with_params(STATIONBOARD_ENDPOINT, args)
-            .or(Err(String::from("url parameters should be parsable")))?;
-
-        get(url)
-            .or_else(|e| Err(format!("could not perform get request: {}", e)))?
-            .json::<StationBoardResponse>()
-            .or_else(|e| Err(format!("could not parse json received: {}", e)))
+    let mut to_write = String::from("");
+    for l in &lines_info {
+        tracing::debug!(
+            line = l.line_number,
+            direction = l.direction,
+            time = l.normal_time,
+            delay = l.delay,
+            "board line"
+        );
+        to_write += &l.to_string();
+        to_write.push('\n');
     }
+
+    let path = Path::new(RESULT_FILE_NAME);
+    let mut file =
+        File::create(path).map_err(|e| format!("couldn't create {}: {}", path.display(), e))?;
+    file.write_all(to_write.as_bytes())
+        .map_err(|e| format!("couldn't write to {}: {}", path.display(), e))?;
+    tracing::info!(file = %path.display(), "successfully wrote the board");
+
+    // only drawn when the layout has room for it
+    let header = (config.layout.header_height > 0)
+        .then(|| board::header(&merged.board, &merged.failures, config, Local::now()));
+    show(config, &lines_info, header)
 }
 
-#[derive(Debug)]
-struct LineInfo {
-    line_number: String,
-    direction: String,
-    normal_departure: String,
-    delay: i32,
+/// Displays the board last displayed again, its header marked stale, when the
+/// layout has one.
+fn display_stale(config: &Config) -> Result<bool, String> {
+    let displayed = Displayed::read(&config.displayed_file)?;
+    let Some(header) = displayed.header.filter(|_| config.layout.header_height > 0) else {
+        return Ok(false);
+    };
+    // the board may have been displayed before later unchanged updates
+    let updated = Health::read(&config.health_file)?
+        .last_success
+        .map(|t| format_time(&t, &config.timezone))
+        .unwrap_or(header.updated);
+    // the stale time already tells that no station could be fetched
+    let header = Header {
+        updated,
+        stale: true,
+        failed: Vec::new(),
+        ..header
+    };
+    show(config, &displayed.lines, Some(header))
 }
 
-impl fmt::Display for LineInfo {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}\t{}\t{}\t{}",
-            self.line_number, self.direction, self.normal_departure, self.delay
-        )
+/// Displays the board unless unchanged, returning whether it did.
+fn show(config: &Config, lines_info: &[LineInfo], header: Option<Header>) -> Result<bool, String> {
+    let hash = board_hash(lines_info, header.as_ref(), &config.layout);
+    let now = Local::now();
+    let displayed = Displayed::read(&config.displayed_file).unwrap_or_else(|e| {
+        tracing::warn!(
+            error = e,
+            "could not read the displayed board, resetting it"
+        );
+        Displayed::default()
+    });
+    if !displayed.needs_display(hash, now, config.forced_display_after) {
+        tracing::info!("the board did not change, leaving the display as it is");
+        return Ok(false);
+    }
+    // an unchanged board displayed again is fully refreshed, in case the
+    // display went wrong
+    display_board(
+        config,
+        lines_info,
+        header.as_ref(),
+        displayed.hash == Some(hash),
+    )?;
+    let displayed = Displayed {
+        hash: Some(hash),
+        at: Some(now),
+        lines: lines_info.to_vec(),
+        header,
+    };
+    if let Err(e) = displayed.write(&config.displayed_file) {
+        tracing::warn!(error = e, "could not write the displayed board");
     }
+    Ok(true)
 }
 
-#[cfg(test)]
-mod tests {
-    use std::{fs::File, io::BufReader, path::Path};
-
-    use super::*;
-
-    const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%z";
-
-    fn coordinate_geneva() -> Coordinate {
-        Coordinate {
-            r#type: String::from("WGS84"),
-            x: Some(46.209751),
-            y: Some(6.14242),
-        }
+/// Draws the board to `IMAGE_FILE`, its accent plane next to it on tri-color
+/// layouts, and pushes it to the e-paper panel, as configured, with a full
+/// refresh when `forced`.
+fn display_board(
+    config: &Config,
+    lines_info: &[LineInfo],
+    header: Option<&Header>,
+    forced: bool,
+) -> Result<(), String> {
+    if config.image_file.is_none() && config.epd.is_none() {
+        return Ok(());
     }
-
-    fn coordinate_zurich() -> Coordinate {
-        Coordinate {
-            r#type: String::from("WGS84"),
-            x: Some(47.377847),
-            y: Some(8.540502),
+    let renderer = Renderer::from_file(&config.font_file, config.layout.style.clone())?;
+    let planes = config.layout.draw_planes(&renderer, header, lines_info);
+    let frame = planes.merged();
+    if let Some(image_file) = &config.image_file {
+        if config.layout.tri_color {
+            planes.black.save(image_file)?;
+            planes.accent.save(&accent_plane_file(image_file))?;
+        } else {
+            frame.save(image_file)?;
         }
+        tracing::info!(file = %image_file.display(), "successfully drew the board");
     }
-
-    fn location_geneva() -> Location {
-        Location {
-            id: Some(String::from("8587057")),
-            name: Some(String::from("Gen\u{00e8}ve, gare Cornavin")),
-            score: None,
-            coordinate: coordinate_geneva(),
-            distance: None,
-        }
+    #[cfg(feature = "epd")]
+    if let Some(backend) = config.epd {
+        display(config, backend, &frame, forced)?;
     }
+    // only the panel is refreshed differently
+    #[cfg(not(feature = "epd"))]
+    let _ = forced;
+    Ok(())
+}
 
-    fn location_zurich() -> Location {
-        Location {
-            id: Some(String::from("8503000")),
-            name: Some(String::from("Z\u{00fc}rich HB")),
-            score: None,
-            coordinate: coordinate_zurich(),
-            distance: None,
-        }
+/// Displays `frame` on the e-paper panel of `backend`, partially refreshing
+/// it from the frame previously displayed when possible and not `forced`.
+#[cfg(feature = "epd")]
+fn display(
+    config: &Config,
+    backend: EpdBackend,
+    frame: &Frame,
+    forced: bool,
+) -> Result<(), String> {
+    let frame_file = displayed_frame_file(&config.epd_state_file);
+    let state = RefreshState::read(&config.epd_state_file).unwrap_or_else(|e| {
+        tracing::warn!(error = e, "could not read the refresh state, resetting it");
+        RefreshState::default()
+    });
+    // an unknown previous frame is fully refreshed
+    let previous = Frame::open(&frame_file).ok();
+    let now = Local::now();
+    let policy = RefreshPolicy {
+        every: config.full_refresh_every,
+        at: config.full_refresh_at.clone(),
+        timezone: config.timezone,
+    };
+    let refresh = if forced {
+        Refresh::Full
+    } else {
+        policy.plan(&state, previous.as_ref(), frame, now)
+    };
+    if refresh == Refresh::Skip {
+        tracing::info!("the displayed board did not change");
+        return Ok(());
     }
-
-    fn prognosis_geneva() -> Prognosis {
-        Prognosis {
-            platform: None,
-            arrival: Some(
-                DateTime::parse_from_str("2024-04-19T12:15:32+0200", TIME_FORMAT)
-                    .unwrap()
-                    .with_timezone(&Local),
-            ),
-            departure: Some(
-                DateTime::parse_from_str("2024-04-19T12:12:00+0200", TIME_FORMAT)
-                    .unwrap()
-                    .with_timezone(&Local),
-            ),
-            capacity1st: None,
-            capacity2nd: None,
-        }
+    // until the refresh succeeds, what the panel displays is unknown
+    if previous.is_some() {
+        std::fs::remove_file(&frame_file)
+            .map_err(|e| format!("could not remove {}: {}", frame_file.display(), e))?;
     }
-
-    fn prognosis_zurich() -> Prognosis {
-        Prognosis {
-            platform: None,
-            arrival: None,
-            departure: Some(
-                DateTime::parse_from_str("2024-04-23T11:38:00+0200", TIME_FORMAT)
-                    .unwrap()
-                    .with_timezone(&Local),
-            ),
-            capacity1st: None,
-            capacity2nd: None,
+    match backend {
+        #[cfg(all(feature = "epd-linux", target_os = "linux"))]
+        EpdBackend::Linux => {
+            api_fetcher::epd::open_linux()?
+                .epd
+                .refresh(&refresh, previous.as_ref(), frame)?
         }
-    }
-
-    fn stop_geneva() -> Stop {
-        Stop {
-            station: location_geneva(),
-            arrival: None,
-            departure: Some(
-                DateTime::parse_from_str("2024-04-19T12:09:00+0200", TIME_FORMAT)
-                    .unwrap()
-                    .with_timezone(&Local),
-            ),
-            delay: Some(3),
-            platform: Some(String::from("F")),
-            prognosis: Some(prognosis_geneva()),
+        #[cfg(not(all(feature = "epd-linux", target_os = "linux")))]
+        EpdBackend::Linux => {
+            return Err(String::from(
+                "EPD=linux requires building on Linux with the epd-linux feature",
+            ))
         }
-    }
-
-    fn stop_zurich() -> Stop {
-        Stop {
-            station: location_zurich(),
-            arrival: None,
-            departure: Some(
-                DateTime::parse_from_str("2024-04-23T11:38:00+0200", TIME_FORMAT)
-                    .unwrap()
-                    .with_timezone(&Local),
-            ),
-            delay: Some(0),
-            platform: Some(String::from("8")),
-            prognosis: Some(prognosis_zurich()),
+        EpdBackend::Simulator => {
+            let simulator = match Frame::open(&config.epd_simulator_file) {
+                Ok(f) => Simulator::with_frame(f),
+                Err(_) => Simulator::new(),
+            };
+            simulator
+                .epd()
+                .refresh(&refresh, previous.as_ref(), frame)?;
+            simulator
+                .frame()
+                .ok_or(String::from("the simulated panel displayed nothing"))?
+                .save(&config.epd_simulator_file)?;
         }
     }
+    frame.save(&frame_file)?;
+    state.record(&refresh, now).write(&config.epd_state_file)?;
+    tracing::info!(?backend, ?refresh, "successfully displayed the board");
+    Ok(())
+}
 
-    #[test]
-    fn parse_coordinates() {
-        let expected = coordinate_geneva();
-        let test_data = r#"
-         {
-            "type": "WGS84",
-            "x": 46.209751,
-            "y": 6.14242
-        }"#;
-
-        let c: Coordinate = serde_json::from_str(test_data).unwrap();
-        assert_eq!(c, expected);
-
-        let expected = coordinate_zurich();
-        let test_data = r#"
-        {
-            "type": "WGS84",
-            "x": 47.377847,
-            "y": 8.540502
-        }"#;
-
-        let c: Coordinate = serde_json::from_str(test_data).unwrap();
-        assert_eq!(c, expected)
-    }
-
-    #[test]
-    fn parse_location() {
-        let expected = location_geneva();
-        let test_data = r#"{
-            "id": "8587057",
-            "name": "Gen\u00e8ve, gare Cornavin",
-            "score": null,
-            "coordinate": {
-                "type": "WGS84",
-                "x": 46.209751,
-                "y": 6.14242
-            },
-            "distance": null
-        }"#;
-
-        let l: Location = serde_json::from_str(test_data).unwrap();
-        assert_eq!(l, expected);
-
-        let expected = location_zurich();
-        let test_data = r#"{
-            "id": "8503000",
-            "name": "Z\u00fcrich HB",
-            "score": null,
-            "coordinate": {
-                "type": "WGS84",
-                "x": 47.377847,
-                "y": 8.540502
-            },
-            "distance": null
-        }"#;
-
-        let l: Location = serde_json::from_str(test_data).unwrap();
-        assert_eq!(l, expected)
+/// Sets the logs up to stderr, at the configured level and format.
+fn init_logs(config: &Config) -> Result<(), String> {
+    let filter =
+        EnvFilter::try_new(&config.log_level).map_err(|e| format!("invalid LOG_LEVEL: {}", e))?;
+    let logs = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    match config.log_format {
+        LogFormat::Pretty => logs.with_ansi(std::io::stderr().is_terminal()).init(),
+        LogFormat::Json => logs.json().init(),
+        LogFormat::Journald => logs.without_time().with_ansi(false).init(),
     }
+    Ok(())
+}
 
-    #[test]
-    fn parse_prognosis() {
-        let expected = prognosis_geneva();
-        let test_data = r#"{
-            "platform": null,
-            "arrival": "2024-04-19T12:15:32+0200",
-            "departure": "2024-04-19T12:12:00+0200",
-            "capacity1st": null,
-            "capacity2nd": null
-        }"#;
-        let p: Prognosis = serde_json::from_str(test_data).unwrap();
-        assert_eq!(p, expected);
-
-        let expected = prognosis_zurich();
-        let test_data = r#"{
-            "platform": null,
-            "arrival": null,
-            "departure": "2024-04-23T11:38:00+0200",
-            "capacity1st": null,
-            "capacity2nd": null
-        }"#;
-        let p: Prognosis = serde_json::from_str(test_data).unwrap();
-        assert_eq!(p, expected);
-    }
+/// Command line arguments.
+#[derive(Debug, PartialEq, Default)]
+struct Args {
+    /// Board datetime, in the configured timezone.
+    at: Option<BoardDateTime>,
+    /// Checks the health file rather than updating the board.
+    health: bool,
+    /// Maximum age of the last successful update for the health check.
+    max_age: Option<chrono::Duration>,
+}
 
-    #[test]
-    fn parse_stop() {
-        let expected = stop_geneva();
-        let test_data = r#"{
-            "station": {
-                "id": "8587057",
-                "name": "Gen\u00e8ve, gare Cornavin",
-                "score": null,
-                "coordinate": {
-                    "type": "WGS84",
-                    "x": 46.209751,
-                    "y": 6.14242
-                },
-                "distance": null
-            },
-            "arrival": null,
-            "arrivalTimestamp": null,
-            "departure": "2024-04-19T12:09:00+0200",
-            "departureTimestamp": 1713521340,
-            "delay": 3,
-            "platform": "F",
-            "prognosis": {
-                "platform": null,
-                "arrival": "2024-04-19T12:15:32+0200",
-                "departure": "2024-04-19T12:12:00+0200",
-                "capacity1st": null,
-                "capacity2nd": null
-            },
-            "realtimeAvailability": null,
-            "location": {
-                "id": "8592899",
-                "name": null,
-                "score": null,
-                "coordinate": {
-                    "type": "WGS84",
-                    "x": null,
-                    "y": null
-                },
-                "distance": null
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut args = args.peekable();
+    let mut parsed = Args {
+        health: args.next_if(|a| a == "health").is_some(),
+        ..Args::default()
+    };
+    while let Some(a) = args.next() {
+        match a.as_str() {
+            "--max-age" if parsed.health => {
+                let m = args
+                    .next()
+                    .and_then(|m| m.parse().ok())
+                    .ok_or(String::from("--max-age expects a number of minutes"))?;
+                parsed.max_age = Some(chrono::Duration::minutes(m));
             }
-        }"#;
-
-        let s: Stop = serde_json::from_str(test_data).unwrap();
-        assert_eq!(s, expected);
-
-        let expected = stop_zurich();
-        let test_data = r#"{
-            "station": {
-                "id": "8503000",
-                "name": "Z\u00fcrich HB",
-                "score": null,
-                "coordinate": {
-                    "type": "WGS84",
-                    "x": 47.377847,
-                    "y": 8.540502
-                },
-                "distance": null
-            },
-            "arrival": null,
-            "arrivalTimestamp": null,
-            "departure": "2024-04-23T11:38:00+0200",
-            "departureTimestamp": 1713865080,
-            "delay": 0,
-            "platform": "8",
-            "prognosis": {
-                "platform": null,
-                "arrival": null,
-                "departure": "2024-04-23T11:38:00+0200",
-                "capacity1st": null,
-                "capacity2nd": null
-            },
-            "realtimeAvailability": null,
-            "location": {
-                "id": "8509000",
-                "name": null,
-                "score": null,
-                "coordinate": {
-                    "type": "WGS84",
-                    "x": null,
-                    "y": null
-                },
-                "distance": null
+            "--at" if !parsed.health => {
+                let d = args.next().ok_or(String::from("--at expects a datetime"))?;
+                parsed.at = Some(parse_board_datetime(&d)?);
             }
-        }"#;
-
-        let s: Stop = serde_json::from_str(test_data).unwrap();
-        assert_eq!(s, expected);
+            _ => return Err(format!("unexpected argument \"{}\"", a)),
+        }
     }
+    Ok(parsed)
+}
 
-    #[test]
-    fn parse_journey() {
-        let expected = Journey {
-            name: Some(String::from("315188")),
-            category: String::from("B"),
-            category_code: None,
-            number: Some(String::from("3")),
-            operator: String::from("TPG"),
-            to: String::from("Grand-Saconnex, Giacometti"),
-            capacity1st: None,
-            capacity2nd: None,
-            pass_list: vec![Stop {
-                station: Location {
-                    id: Some(String::from("8592899")),
-                    name: None,
-                    score: None,
-                    coordinate: Coordinate {
-                        r#type: String::from("WGS84"),
-                        x: None,
-                        y: None,
-                    },
-                    distance: None,
-                },
-                arrival: None,
-                departure: Some(
-                    DateTime::parse_from_str("2024-04-19T12:09:00+0200", TIME_FORMAT)
-                        .unwrap()
-                        .with_timezone(&Local),
-                ),
-                delay: Some(3),
-                platform: Some(String::from("F")),
-                prognosis: Some(prognosis_geneva()),
-            }],
-        };
-        let test_data = r#"{
-            "stop": {
-                "station": {
-                    "id": "8587057",
-                    "name": "Gen\u00e8ve, gare Cornavin",
-                    "score": null,
-                    "coordinate": {
-                        "type": "WGS84",
-                        "x": 46.209751,
-                        "y": 6.14242
-                    },
-                    "distance": null
-                },
-                "arrival": null,
-                "arrivalTimestamp": null,
-                "departure": "2024-04-19T12:09:00+0200",
-                "departureTimestamp": 1713521340,
-                "delay": 3,
-                "platform": "F",
-                "prognosis": {
-                    "platform": null,
-                    "arrival": "2024-04-19T12:15:32+0200",
-                    "departure": "2024-04-19T12:12:00+0200",
-                    "capacity1st": null,
-                    "capacity2nd": null
-                },
-                "realtimeAvailability": null,
-                "location": {
-                    "id": "8592899",
-                    "name": null,
-                    "score": null,
-                    "coordinate": {
-                        "type": "WGS84",
-                        "x": null,
-                        "y": null
-                    },
-                    "distance": null
-                }
-            },
-            "name": "315188",
-            "category": "B",
-            "subcategory": null,
-            "categoryCode": null,
-            "number": "3",
-            "operator": "TPG",
-            "to": "Grand-Saconnex, Giacometti",
-            "passList": [
-                {
-                    "station": {
-                        "id": "8592899",
-                        "name": null,
-                        "score": null,
-                        "coordinate": {
-                            "type": "WGS84",
-                            "x": null,
-                            "y": null
-                        },
-                        "distance": null
-                    },
-                    "arrival": null,
-                    "arrivalTimestamp": null,
-                    "departure": "2024-04-19T12:09:00+0200",
-                    "departureTimestamp": 1713521340,
-                    "delay": 3,
-                    "platform": "F",
-                    "prognosis": {
-                        "platform": null,
-                        "arrival": "2024-04-19T12:15:32+0200",
-                        "departure": "2024-04-19T12:12:00+0200",
-                        "capacity1st": null,
-                        "capacity2nd": null
-                    },
-                    "realtimeAvailability": null,
-                    "location": {
-                        "id": "8592899",
-                        "name": null,
-                        "score": null,
-                        "coordinate": {
-                            "type": "WGS84",
-                            "x": null,
-                            "y": null
-                        },
-                        "distance": null
-                    }
-                }
-            ],
-            "capacity1st": null,
-            "capacity2nd": null
-        }"#;
-        let j: Journey = serde_json::from_str(test_data).unwrap();
-
-        assert_eq!(j, expected);
-
-        let expected = Journey {
-            name: Some(String::from("000567")),
-            category: String::from("IC"),
-            category_code: None,
-            number: Some(String::from("3")),
-            operator: String::from("SBB"),
-            to: String::from("Chur"),
-            capacity1st: None,
-            capacity2nd: None,
-            pass_list: vec![Stop {
-                station: Location {
-                    id: Some(String::from("8509000")),
-                    name: None,
-                    score: None,
-                    coordinate: Coordinate {
-                        r#type: String::from("WGS84"),
-                        x: None,
-                        y: None,
-                    },
-                    distance: None,
-                },
-                arrival: None,
-                departure: Some(
-                    DateTime::parse_from_str("2024-04-23T11:38:00+0200", TIME_FORMAT)
-                        .unwrap()
-                        .with_timezone(&Local),
-                ),
-                delay: Some(0),
-                platform: Some(String::from("8")),
-                prognosis: Some(prognosis_zurich()),
-            }],
-        };
-        let test_data = r#"{
-            "stop": {
-                "station": {
-                    "id": "8503000",
-                    "name": "Z\u00fcrich HB",
-                    "score": null,
-                    "coordinate": {
-                        "type": "WGS84",
-                        "x": 47.377847,
-                        "y": 8.540502
-                    },
-                    "distance": null
-                },
-                "arrival": null,
-                "arrivalTimestamp": null,
-                "departure": "2024-04-23T11:38:00+0200",
-                "departureTimestamp": 1713865080,
-                "delay": 0,
-                "platform": "8",
-                "prognosis": {
-                    "platform": null,
-                    "arrival": null,
-                    "departure": "2024-04-23T11:38:00+0200",
-                    "capacity1st": null,
-                    "capacity2nd": null
-                },
-                "realtimeAvailability": null,
-                "location": {
-                    "id": "8509000",
-                    "name": null,
-                    "score": null,
-                    "coordinate": {
-                        "type": "WGS84",
-                        "x": null,
-                        "y": null
-                    },
-                    "distance": null
-                }
-            },
-            "name": "000567",
-            "category": "IC",
-            "subcategory": null,
-            "categoryCode": null,
-            "number": "3",
-            "operator": "SBB",
-            "to": "Chur",
-            "passList": [
-                {
-                    "station": {
-                        "id": "8509000",
-                        "name": null,
-                        "score": null,
-                        "coordinate": {
-                            "type": "WGS84",
-                            "x": null,
-                            "y": null
-                        },
-                        "distance": null
-                    },
-                    "arrival": null,
-                    "arrivalTimestamp": null,
-                    "departure": "2024-04-23T11:38:00+0200",
-                    "departureTimestamp": 1713865080,
-                    "delay": 0,
-                    "platform": "8",
-                    "prognosis": {
-                        "platform": null,
-                        "arrival": null,
-                        "departure": "2024-04-23T11:38:00+0200",
-                        "capacity1st": null,
-                        "capacity2nd": null
-                    },
-                    "realtimeAvailability": null,
-                    "location": {
-                        "id": "8509000",
-                        "name": null,
-                        "score": null,
-                        "coordinate": {
-                            "type": "WGS84",
-                            "x": null,
-                            "y": null
-                        },
-                        "distance": null
-                    }
-                }],
-            "capacity1st": null,
-            "capacity2nd": null
-        }"#;
-        let j: Journey = serde_json::from_str(test_data).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDateTime, NaiveTime};
 
-        assert_eq!(j, expected)
+    fn time(s: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(s, "%H:%M").unwrap()
     }
 
     #[test]
-    fn parse_stationboard() {
-        let file =
-            File::open(Path::new(env!("CARGO_MANIFEST_DIR")).join("geneve_cornavin_test.json"))
-                .unwrap();
-        let reader = BufReader::new(file);
-        let s: StationBoardResponse = serde_json::from_reader(reader).unwrap();
-        assert_eq!(s.station.coordinate, coordinate_geneva());
-        assert_eq!(s.station, location_geneva());
+    fn parse_command_line() {
+        let args = |a: &[&str]| parse_args(a.iter().map(|s| s.to_string()));
+        assert_eq!(args(&[]), Ok(Args::default()));
         assert_eq!(
-            s.stationboard[0].clone().stop.prognosis.unwrap(),
-            prognosis_geneva()
+            args(&["--at", "2024-04-20 07:30"]),
+            Ok(Args {
+                at: Some(BoardDateTime::At(
+                    NaiveDateTime::parse_from_str("2024-04-20 07:30", "%Y-%m-%d %H:%M").unwrap()
+                )),
+                ..Args::default()
+            })
         );
-        assert_eq!(s.stationboard[0].clone().stop, stop_geneva());
-
-        let file =
-            File::open(Path::new(env!("CARGO_MANIFEST_DIR")).join("zurich_hb_test.json")).unwrap();
-        let reader = BufReader::new(file);
-        let s: StationBoardResponse = serde_json::from_reader(reader).unwrap();
-        assert_eq!(s.station.coordinate, coordinate_zurich());
-        assert_eq!(s.station, location_zurich());
         assert_eq!(
-            s.stationboard[0].clone().stop.prognosis.unwrap(),
-            prognosis_zurich()
+            args(&["--at", "07:30"]),
+            Ok(Args {
+                at: Some(BoardDateTime::Next(time("07:30"))),
+                ..Args::default()
+            })
         );
-        assert_eq!(s.stationboard[0].clone().stop, stop_zurich());
-    }
+        assert!(args(&["--at"]).is_err());
+        assert!(args(&["--at", "tomorrow"]).is_err());
+        assert!(args(&["--verbose"]).is_err());
 
-    #[test]
-    fn test_api_call_all_params() {
-        StationBoardResponse::get(
-            Some("Genève, Cornavin"),
-            Some("8587057"),
-            Some(3),
-            Some(vec!["metro", "tram"]),
-            Some(chrono::Local::now()),
-            Some("arrival"),
-        )
-        .expect("error with the API call");
-    }
-
-    #[test]
-    fn test_api_different_stations() {
-        for s in [
-            "Genève, gare Cornavin",
-            "Zürich HB",
-            "Lausanne, gare",
-            "Bern, Bahnhof",
-        ] {
-            StationBoardResponse::get(Some(s), None, None, None, None, None)
-                .expect(&format!("error with the API call for station {}", s));
-        }
+        assert_eq!(
+            args(&["health", "--max-age", "30"]),
+            Ok(Args {
+                health: true,
+                max_age: Some(chrono::Duration::minutes(30)),
+                ..Args::default()
+            })
+        );
+        assert!(args(&["health", "--at", "07:30"]).is_err());
+        assert!(args(&["--max-age", "30"]).is_err());
+        assert!(args(&["--at", "07:30", "health"]).is_err());
     }
 }
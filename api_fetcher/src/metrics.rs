@@ -0,0 +1,189 @@
+//! Prometheus metrics of the fetches, served over HTTP on `/metrics` for boards
+//! running as a daemon.
+
+use crate::board::MergedBoard;
+use prometheus::{
+    Encoder, Gauge, Histogram, HistogramOpts, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const METRICS_PATH: &str = "/metrics";
+
+struct Metrics {
+    registry: Registry,
+    fetches: IntCounterVec,
+    request_duration: Histogram,
+    last_success: Gauge,
+    board_age: Gauge,
+    rate_limit_remaining: IntGauge,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(|| {
+    let registry = Registry::new_custom(Some(String::from("api_fetcher")), None)
+        .expect("metrics prefix should be valid");
+    let fetches = IntCounterVec::new(
+        Opts::new("fetches_total", "Board fetches of each station by result"),
+        &["station", "result"],
+    )
+    .expect("fetches metric should be valid");
+    let request_duration = Histogram::with_opts(
+        HistogramOpts::new(
+            "request_duration_seconds",
+            "Latency of the stationboard requests",
+        )
+        .buckets(vec![0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]),
+    )
+    .expect("request duration metric should be valid");
+    let last_success = Gauge::new(
+        "last_success_timestamp_seconds",
+        "Unix time of the last board fetched",
+    )
+    .expect("last success metric should be valid");
+    let board_age = Gauge::new("board_age_seconds", "Age of the board last fetched")
+        .expect("board age metric should be valid");
+    let rate_limit_remaining = IntGauge::new(
+        "rate_limit_remaining",
+        "Requests left in the API rate limit budget",
+    )
+    .expect("rate limit metric should be valid");
+    for m in [
+        Box::new(fetches.clone()) as Box<dyn prometheus::core::Collector>,
+        Box::new(request_duration.clone()),
+        Box::new(last_success.clone()),
+        Box::new(board_age.clone()),
+        Box::new(rate_limit_remaining.clone()),
+    ] {
+        registry
+            .register(m)
+            .expect("metrics should be registered once");
+    }
+    Metrics {
+        registry,
+        fetches,
+        request_duration,
+        last_success,
+        board_age,
+        rate_limit_remaining,
+    }
+});
+
+/// Records a stationboard request, `rate_limit_remaining` being the value of
+/// the [`RATE_LIMIT_REMAINING_HEADER`](crate::client::RATE_LIMIT_REMAINING_HEADER)
+/// when the API sent it.
+pub fn observe_request(duration: Duration, rate_limit_remaining: Option<i64>) {
+    METRICS.request_duration.observe(duration.as_secs_f64());
+    if let Some(r) = rate_limit_remaining {
+        METRICS.rate_limit_remaining.set(r);
+    }
+}
+
+/// Records the result of fetching the board of `station_ids`.
+pub fn observe_fetch(station_ids: &[String], result: &Result<MergedBoard, String>) {
+    for id in station_ids {
+        let failed = match result {
+            Ok(m) => m.failures.iter().any(|f| &f.station_id == id),
+            Err(_) => true,
+        };
+        let label = if failed { "failure" } else { "success" };
+        METRICS
+            .fetches
+            .with_label_values(&[id.as_str(), label])
+            .inc();
+    }
+    if result.is_ok() {
+        METRICS.last_success.set(unix_now());
+    }
+}
+
+/// Metrics in the prometheus text format.
+pub fn gather() -> Result<String, String> {
+    let last_success = METRICS.last_success.get();
+    if last_success > 0.0 {
+        METRICS.board_age.set(unix_now() - last_success);
+    }
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&METRICS.registry.gather(), &mut buffer)
+        .map_err(|e| format!("could not encode metrics: {}", e))?;
+    String::from_utf8(buffer).map_err(|e| format!("could not encode metrics: {}", e))
+}
+
+/// Serves the metrics on `address` from a background thread.
+pub fn serve(address: SocketAddr) -> Result<std::thread::JoinHandle<()>, String> {
+    let server = tiny_http::Server::http(address)
+        .map_err(|e| format!("could not listen on {}: {}", address, e))?;
+    tracing::info!(%address, "serving metrics");
+    Ok(std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = if request.url() != METRICS_PATH {
+                tiny_http::Response::from_string("not found").with_status_code(404)
+            } else {
+                match gather() {
+                    Ok(m) => tiny_http::Response::from_string(m),
+                    Err(e) => {
+                        tracing::error!(error = e, "could not gather metrics");
+                        tiny_http::Response::from_string(e).with_status_code(500)
+                    }
+                }
+            };
+            if let Err(e) = request.respond(response) {
+                tracing::warn!(error = %e, "could not send metrics");
+            }
+        }
+    }))
+}
+
+fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::merge_stations;
+    use crate::fixtures::stationboard_geneva;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn gather_fetches() {
+        let ids = vec![String::from("8587057"), String::from("8503000")];
+        let merged = merge_stations(vec![
+            (ids[0].clone(), Ok(stationboard_geneva())),
+            (ids[1].clone(), Err(String::from("timed out"))),
+        ]);
+        observe_fetch(&ids, &merged);
+        observe_request(Duration::from_millis(300), Some(42));
+        let m = gather().unwrap();
+        assert!(m.contains("api_fetcher_fetches_total{result=\"success\",station=\"8587057\"}"));
+        assert!(m.contains("api_fetcher_fetches_total{result=\"failure\",station=\"8503000\"}"));
+        assert!(m.contains("api_fetcher_request_duration_seconds_bucket{le=\"0.5\"}"));
+        assert!(m.contains("api_fetcher_rate_limit_remaining 42"));
+        assert!(m.contains("api_fetcher_board_age_seconds "));
+    }
+
+    #[test]
+    fn serve_metrics() {
+        // lets the OS pick a free port by binding it first
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        serve(address).unwrap();
+        let get = |path: &str| {
+            let mut stream = std::net::TcpStream::connect(address).unwrap();
+            write!(stream, "GET {} HTTP/1.0\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let m = get(METRICS_PATH);
+        assert!(m.starts_with("HTTP/1.0 200"), "{}", m);
+        assert!(m.contains("api_fetcher_request_duration_seconds"));
+        assert!(get("/").starts_with("HTTP/1.0 404"));
+    }
+}
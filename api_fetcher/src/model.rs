@@ -0,0 +1,1287 @@
+//! Types of the stationboard endpoint responses, following the documented
+//! schema of <https://transport.opendata.ch/docs.html>.
+
+use chrono::{DateTime, Local};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+// entries can be several kilobytes with their pass list
+const MALFORMED_SNIPPET_MAX_CHARS: usize = 300;
+
+/// Coordinates of a location, `x` being the latitude and `y` the longitude.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Coordinate {
+    pub r#type: Option<String>,
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+}
+
+/// A station, point of interest or address.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Location {
+    pub id: Option<String>,
+    // station, poi, address or refine, only returned by some endpoints
+    pub r#type: Option<String>,
+    pub name: Option<String>,
+    pub score: Option<f32>,
+    pub coordinate: Coordinate,
+    pub distance: Option<f32>,
+    pub icon: Option<String>,
+}
+
+/// Realtime expectations of a stop.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Prognosis {
+    pub platform: Option<String>,
+    pub departure: Option<DateTime<Local>>,
+    pub arrival: Option<DateTime<Local>>,
+    pub capacity1st: Option<u32>,
+    pub capacity2nd: Option<u32>,
+}
+
+/// A journey stopping at a station.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Stop {
+    pub station: Location,
+    pub arrival: Option<DateTime<Local>>,
+    pub arrival_timestamp: Option<i64>,
+    pub departure: Option<DateTime<Local>>,
+    pub departure_timestamp: Option<i64>,
+    pub delay: Option<i32>,
+    pub platform: Option<String>,
+    pub prognosis: Option<Prognosis>,
+    pub realtime_availability: Option<String>,
+    pub location: Option<Location>,
+}
+
+impl Stop {
+    /// Scheduled arrival, falling back on the unix timestamp when the API
+    /// omits the ISO string.
+    pub fn arrival_time(&self) -> Option<DateTime<Local>> {
+        self.arrival
+            .or_else(|| timestamp_to_local(self.arrival_timestamp?))
+    }
+
+    /// Scheduled departure, falling back on the unix timestamp when the API
+    /// omits the ISO string.
+    pub fn departure_time(&self) -> Option<DateTime<Local>> {
+        self.departure
+            .or_else(|| timestamp_to_local(self.departure_timestamp?))
+    }
+
+    /// Whether the expected platform differs from the scheduled one.
+    pub fn platform_changed(&self) -> bool {
+        let expected = self.prognosis.as_ref().and_then(|p| p.platform.as_ref());
+        matches!((expected, &self.platform), (Some(e), Some(p)) if e != p)
+    }
+}
+
+fn timestamp_to_local(timestamp: i64) -> Option<DateTime<Local>> {
+    DateTime::from_timestamp(timestamp, 0).map(|t| t.with_timezone(&Local))
+}
+
+/// A vehicle run of a line, e.g. the bus 3 towards Grand-Saconnex.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Journey {
+    pub name: Option<String>,
+    pub category: Option<String>,
+    pub subcategory: Option<String>,
+    pub category_code: Option<String>,
+    pub number: Option<String>,
+    pub operator: Option<String>,
+    pub to: Option<String>,
+    pub capacity1st: Option<u32>,
+    pub capacity2nd: Option<u32>,
+    pub pass_list: Vec<Stop>,
+}
+
+impl Journey {
+    /// Line number of the journey as displayed, falling back on its category
+    /// as some trains only have their line there, e.g. "S5".
+    pub fn line_number(&self) -> Option<&str> {
+        self.number.as_deref().or(self.category.as_deref())
+    }
+
+    /// Transport mode of the journey, from its category or, failing that, its
+    /// category code.
+    pub fn transport_mode(&self) -> Option<TransportMode> {
+        self.category
+            .as_deref()
+            .and_then(TransportMode::from_category)
+            .or_else(|| {
+                self.category_code
+                    .as_deref()
+                    .and_then(TransportMode::from_category_code)
+            })
+    }
+}
+
+/// Transport mode of a journey.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportMode {
+    Train,
+    Tram,
+    Bus,
+    Ship,
+    Cableway,
+    Metro,
+}
+
+impl TransportMode {
+    /// Value of the mode in the API `transportations[]` parameter.
+    pub fn api_value(&self) -> &'static str {
+        match self {
+            TransportMode::Train => "train",
+            TransportMode::Tram => "tram",
+            TransportMode::Bus => "bus",
+            TransportMode::Ship => "ship",
+            TransportMode::Cableway => "cableway",
+            TransportMode::Metro => "metro",
+        }
+    }
+
+    /// Parses a journey category, e.g. `IC`, `S` or `S5` for trains, `B` for
+    /// buses or `T` for trams.
+    pub fn from_category(category: &str) -> Option<Self> {
+        let category = category.trim_end_matches(|c: char| c.is_ascii_digit());
+        match category.to_uppercase().as_str() {
+            "ICE" | "TGV" | "RJ" | "RJX" | "EC" | "EN" | "NJ" | "IC" | "ICN" | "IR" | "IRE"
+            | "RE" | "D" | "R" | "S" | "SN" | "PE" | "EXT" | "ARZ" | "CC" | "TER" | "TRN" => {
+                Some(TransportMode::Train)
+            }
+            "T" | "TRAM" | "NFT" => Some(TransportMode::Tram),
+            "B" | "BUS" | "NFB" | "KB" | "BN" | "EV" | "TX" => Some(TransportMode::Bus),
+            "BAT" | "BAV" | "FAE" | "SHIP" => Some(TransportMode::Ship),
+            "PB" | "GB" | "SL" | "LB" | "FUN" => Some(TransportMode::Cableway),
+            "M" | "METRO" => Some(TransportMode::Metro),
+            _ => None,
+        }
+    }
+
+    /// Parses the legacy numeric category code, indexing the historical
+    /// transportations `ice_tgv_rj, ec_ic, ir, re_d, ship, s_sn_r, bus,
+    /// cableway, arz_ext, tram`.
+    pub fn from_category_code(code: &str) -> Option<Self> {
+        match code.trim() {
+            "0" | "1" | "2" | "3" | "5" | "8" => Some(TransportMode::Train),
+            "4" => Some(TransportMode::Ship),
+            "6" => Some(TransportMode::Bus),
+            "7" => Some(TransportMode::Cableway),
+            "9" => Some(TransportMode::Tram),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for TransportMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "train" => Ok(TransportMode::Train),
+            "tram" => Ok(TransportMode::Tram),
+            "bus" => Ok(TransportMode::Bus),
+            "ship" | "boat" => Ok(TransportMode::Ship),
+            "cableway" => Ok(TransportMode::Cableway),
+            "metro" => Ok(TransportMode::Metro),
+            _ => Err(format!("unknown transport mode \"{}\"", s)),
+        }
+    }
+}
+
+impl fmt::Display for TransportMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.api_value())
+    }
+}
+
+/// Parses a `,` separated list of transport modes, e.g. `bus,tram`.
+pub fn parse_transport_modes(s: &str) -> Result<Vec<TransportMode>, String> {
+    s.split(',')
+        .filter(|m| !m.trim().is_empty())
+        .map(TransportMode::from_str)
+        .collect()
+}
+
+/// Whether a board lists the departures or the arrivals of a station.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+pub enum BoardType {
+    #[default]
+    Departure,
+    Arrival,
+}
+
+impl BoardType {
+    /// Value of the type in the API `type` parameter.
+    pub fn api_value(&self) -> &'static str {
+        match self {
+            BoardType::Departure => "departure",
+            BoardType::Arrival => "arrival",
+        }
+    }
+}
+
+impl FromStr for BoardType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "departure" => Ok(BoardType::Departure),
+            "arrival" => Ok(BoardType::Arrival),
+            _ => Err(format!("unknown board type \"{}\"", s)),
+        }
+    }
+}
+
+impl fmt::Display for BoardType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.api_value())
+    }
+}
+
+/// Parses a `,` separated list of board types, `both` standing for
+/// `departure,arrival`.
+pub fn parse_board_types(s: &str) -> Result<Vec<BoardType>, String> {
+    if s.trim().eq_ignore_ascii_case("both") {
+        return Ok(vec![BoardType::Departure, BoardType::Arrival]);
+    }
+    s.split(',')
+        .filter(|t| !t.trim().is_empty())
+        .map(BoardType::from_str)
+        .collect()
+}
+
+/// A journey with its stop at the board station.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct StationBoardElement {
+    pub stop: Stop,
+    #[serde(flatten)]
+    pub journey: Journey,
+    // not part of the API response, set from the type of the requested board
+    #[serde(skip)]
+    pub board_type: BoardType,
+}
+
+impl StationBoardElement {
+    /// Scheduled arrival or departure time, depending on the board type.
+    pub fn time(&self) -> Option<DateTime<Local>> {
+        match self.board_type {
+            BoardType::Departure => self.stop.departure_time(),
+            BoardType::Arrival => self.stop.arrival_time(),
+        }
+    }
+
+    /// Delay in minutes. Arrivals compute it from the prognosis as the API
+    /// delay refers to the departure, without delay when their arrival has no
+    /// prognosis.
+    pub fn delay(&self) -> Option<i32> {
+        match self.board_type {
+            BoardType::Departure => self.stop.delay,
+            BoardType::Arrival => {
+                let expected = self.stop.prognosis.as_ref()?.arrival?;
+                Some((expected - self.stop.arrival_time()?).num_minutes() as i32)
+            }
+        }
+    }
+
+    /// Whether the stop has a realtime prognosis of its time, its delay being
+    /// 0 otherwise.
+    pub fn has_realtime(&self) -> bool {
+        self.stop
+            .prognosis
+            .as_ref()
+            .is_some_and(|p| match self.board_type {
+                BoardType::Departure => p.departure.is_some(),
+                BoardType::Arrival => p.arrival.is_some(),
+            })
+    }
+
+    /// Destination of a departure, or origin of an arrival: the first named
+    /// stop of its pass list other than the board station, falling back on
+    /// the destination when it is unknown.
+    pub fn destination_or_origin(&self, station: &Location) -> Option<&str> {
+        let origin = match self.board_type {
+            BoardType::Departure => None,
+            BoardType::Arrival => self
+                .journey
+                .pass_list
+                .iter()
+                .filter(|s| s.station.id != station.id)
+                .find_map(|s| s.station.name.as_deref()),
+        };
+        origin.or(self.journey.to.as_deref())
+    }
+}
+
+/// Response of the stationboard endpoint.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct StationBoardResponse {
+    pub station: Location,
+    #[serde(deserialize_with = "deserialize_skipping_malformed")]
+    pub stationboard: Vec<StationBoardElement>,
+}
+
+/// Deserializes a list element by element, skipping and logging the malformed
+/// ones so that a single unexpected entry does not fail the whole response.
+fn deserialize_skipping_malformed<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let values = Vec::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(values
+        .into_iter()
+        .filter_map(|v| match T::deserialize(&v) {
+            Ok(e) => Some(e),
+            Err(e) => {
+                let raw = v.to_string();
+                let snippet: String = raw.chars().take(MALFORMED_SNIPPET_MAX_CHARS).collect();
+                tracing::warn!(error = %e, entry = snippet, "skipping malformed entry");
+                None
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::BufReader, path::Path};
+
+    use super::*;
+    use crate::fixtures::{local_time, stationboard_geneva, stationboard_zurich, TIME_FORMAT};
+
+    #[allow(clippy::excessive_precision)]
+    fn coordinate_geneva() -> Coordinate {
+        Coordinate {
+            r#type: Some(String::from("WGS84")),
+            x: Some(46.209751),
+            y: Some(6.14242),
+        }
+    }
+
+    fn coordinate_zurich() -> Coordinate {
+        Coordinate {
+            r#type: Some(String::from("WGS84")),
+            x: Some(47.377847),
+            y: Some(8.540502),
+        }
+    }
+
+    fn location_geneva() -> Location {
+        Location {
+            id: Some(String::from("8587057")),
+            r#type: None,
+            name: Some(String::from("Gen\u{00e8}ve, gare Cornavin")),
+            score: None,
+            coordinate: coordinate_geneva(),
+            distance: None,
+            icon: None,
+        }
+    }
+
+    fn location_zurich() -> Location {
+        Location {
+            id: Some(String::from("8503000")),
+            r#type: None,
+            name: Some(String::from("Z\u{00fc}rich HB")),
+            score: None,
+            coordinate: coordinate_zurich(),
+            distance: None,
+            icon: None,
+        }
+    }
+
+    fn location_without_name(id: &str) -> Location {
+        Location {
+            id: Some(String::from(id)),
+            r#type: None,
+            name: None,
+            score: None,
+            coordinate: Coordinate {
+                r#type: Some(String::from("WGS84")),
+                x: None,
+                y: None,
+            },
+            distance: None,
+            icon: None,
+        }
+    }
+
+    fn prognosis_geneva() -> Prognosis {
+        Prognosis {
+            platform: None,
+            arrival: Some(
+                DateTime::parse_from_str("2024-04-19T12:15:32+0200", TIME_FORMAT)
+                    .unwrap()
+                    .with_timezone(&Local),
+            ),
+            departure: Some(
+                DateTime::parse_from_str("2024-04-19T12:12:00+0200", TIME_FORMAT)
+                    .unwrap()
+                    .with_timezone(&Local),
+            ),
+            capacity1st: None,
+            capacity2nd: None,
+        }
+    }
+
+    fn prognosis_zurich() -> Prognosis {
+        Prognosis {
+            platform: None,
+            arrival: None,
+            departure: Some(
+                DateTime::parse_from_str("2024-04-23T11:38:00+0200", TIME_FORMAT)
+                    .unwrap()
+                    .with_timezone(&Local),
+            ),
+            capacity1st: None,
+            capacity2nd: None,
+        }
+    }
+
+    fn stop_geneva() -> Stop {
+        Stop {
+            station: location_geneva(),
+            arrival: None,
+            arrival_timestamp: None,
+            departure: Some(
+                DateTime::parse_from_str("2024-04-19T12:09:00+0200", TIME_FORMAT)
+                    .unwrap()
+                    .with_timezone(&Local),
+            ),
+            departure_timestamp: Some(1713521340),
+            delay: Some(3),
+            platform: Some(String::from("F")),
+            prognosis: Some(prognosis_geneva()),
+            realtime_availability: None,
+            location: Some(location_without_name("8592899")),
+        }
+    }
+
+    fn stop_zurich() -> Stop {
+        Stop {
+            station: location_zurich(),
+            arrival: None,
+            arrival_timestamp: None,
+            departure: Some(
+                DateTime::parse_from_str("2024-04-23T11:38:00+0200", TIME_FORMAT)
+                    .unwrap()
+                    .with_timezone(&Local),
+            ),
+            departure_timestamp: Some(1713865080),
+            delay: Some(0),
+            platform: Some(String::from("8")),
+            prognosis: Some(prognosis_zurich()),
+            realtime_availability: None,
+            location: Some(location_without_name("8509000")),
+        }
+    }
+
+    #[test]
+    fn parse_coordinates() {
+        let expected = coordinate_geneva();
+        let test_data = r#"
+         {
+            "type": "WGS84",
+            "x": 46.209751,
+            "y": 6.14242
+        }"#;
+
+        let c: Coordinate = serde_json::from_str(test_data).unwrap();
+        assert_eq!(c, expected);
+
+        let expected = coordinate_zurich();
+        let test_data = r#"
+        {
+            "type": "WGS84",
+            "x": 47.377847,
+            "y": 8.540502
+        }"#;
+
+        let c: Coordinate = serde_json::from_str(test_data).unwrap();
+        assert_eq!(c, expected)
+    }
+
+    #[test]
+    fn parse_location() {
+        let expected = location_geneva();
+        let test_data = r#"{
+            "id": "8587057",
+            "name": "Gen\u00e8ve, gare Cornavin",
+            "score": null,
+            "coordinate": {
+                "type": "WGS84",
+                "x": 46.209751,
+                "y": 6.14242
+            },
+            "distance": null
+        }"#;
+
+        let l: Location = serde_json::from_str(test_data).unwrap();
+        assert_eq!(l, expected);
+
+        let expected = location_zurich();
+        let test_data = r#"{
+            "id": "8503000",
+            "name": "Z\u00fcrich HB",
+            "score": null,
+            "coordinate": {
+                "type": "WGS84",
+                "x": 47.377847,
+                "y": 8.540502
+            },
+            "distance": null
+        }"#;
+
+        let l: Location = serde_json::from_str(test_data).unwrap();
+        assert_eq!(l, expected)
+    }
+
+    #[test]
+    fn parse_location_all_fields() {
+        let expected = Location {
+            id: Some(String::from("8591123")),
+            r#type: Some(String::from("station")),
+            name: Some(String::from("Z\u{00fc}rich, Bahnhofstrasse/HB")),
+            score: Some(101.0),
+            coordinate: Coordinate {
+                r#type: Some(String::from("WGS84")),
+                x: Some(47.37431),
+                y: Some(8.539_09),
+            },
+            distance: Some(245.0),
+            icon: Some(String::from("tram")),
+        };
+        let test_data = r#"{
+            "id": "8591123",
+            "type": "station",
+            "name": "Z\u00fcrich, Bahnhofstrasse/HB",
+            "score": 101,
+            "coordinate": {
+                "type": "WGS84",
+                "x": 47.37431,
+                "y": 8.53909
+            },
+            "distance": 245,
+            "icon": "tram"
+        }"#;
+
+        let l: Location = serde_json::from_str(test_data).unwrap();
+        assert_eq!(l, expected)
+    }
+
+    #[test]
+    fn parse_prognosis() {
+        let expected = prognosis_geneva();
+        let test_data = r#"{
+            "platform": null,
+            "arrival": "2024-04-19T12:15:32+0200",
+            "departure": "2024-04-19T12:12:00+0200",
+            "capacity1st": null,
+            "capacity2nd": null
+        }"#;
+        let p: Prognosis = serde_json::from_str(test_data).unwrap();
+        assert_eq!(p, expected);
+
+        let expected = prognosis_zurich();
+        let test_data = r#"{
+            "platform": null,
+            "arrival": null,
+            "departure": "2024-04-23T11:38:00+0200",
+            "capacity1st": null,
+            "capacity2nd": null
+        }"#;
+        let p: Prognosis = serde_json::from_str(test_data).unwrap();
+        assert_eq!(p, expected);
+    }
+
+    #[test]
+    fn parse_stop() {
+        let expected = stop_geneva();
+        let test_data = r#"{
+            "station": {
+                "id": "8587057",
+                "name": "Gen\u00e8ve, gare Cornavin",
+                "score": null,
+                "coordinate": {
+                    "type": "WGS84",
+                    "x": 46.209751,
+                    "y": 6.14242
+                },
+                "distance": null
+            },
+            "arrival": null,
+            "arrivalTimestamp": null,
+            "departure": "2024-04-19T12:09:00+0200",
+            "departureTimestamp": 1713521340,
+            "delay": 3,
+            "platform": "F",
+            "prognosis": {
+                "platform": null,
+                "arrival": "2024-04-19T12:15:32+0200",
+                "departure": "2024-04-19T12:12:00+0200",
+                "capacity1st": null,
+                "capacity2nd": null
+            },
+            "realtimeAvailability": null,
+            "location": {
+                "id": "8592899",
+                "name": null,
+                "score": null,
+                "coordinate": {
+                    "type": "WGS84",
+                    "x": null,
+                    "y": null
+                },
+                "distance": null
+            }
+        }"#;
+
+        let s: Stop = serde_json::from_str(test_data).unwrap();
+        assert_eq!(s, expected);
+
+        let expected = stop_zurich();
+        let test_data = r#"{
+            "station": {
+                "id": "8503000",
+                "name": "Z\u00fcrich HB",
+                "score": null,
+                "coordinate": {
+                    "type": "WGS84",
+                    "x": 47.377847,
+                    "y": 8.540502
+                },
+                "distance": null
+            },
+            "arrival": null,
+            "arrivalTimestamp": null,
+            "departure": "2024-04-23T11:38:00+0200",
+            "departureTimestamp": 1713865080,
+            "delay": 0,
+            "platform": "8",
+            "prognosis": {
+                "platform": null,
+                "arrival": null,
+                "departure": "2024-04-23T11:38:00+0200",
+                "capacity1st": null,
+                "capacity2nd": null
+            },
+            "realtimeAvailability": null,
+            "location": {
+                "id": "8509000",
+                "name": null,
+                "score": null,
+                "coordinate": {
+                    "type": "WGS84",
+                    "x": null,
+                    "y": null
+                },
+                "distance": null
+            }
+        }"#;
+
+        let s: Stop = serde_json::from_str(test_data).unwrap();
+        assert_eq!(s, expected);
+    }
+
+    #[test]
+    fn parse_stop_all_fields() {
+        let expected = Stop {
+            station: location_without_name("8503000"),
+            arrival: Some(local_time("2024-04-23T11:32:00+0200")),
+            arrival_timestamp: Some(1713864720),
+            departure: Some(local_time("2024-04-23T11:38:00+0200")),
+            departure_timestamp: Some(1713865080),
+            delay: Some(2),
+            platform: Some(String::from("8")),
+            prognosis: Some(Prognosis {
+                platform: Some(String::from("9")),
+                arrival: Some(local_time("2024-04-23T11:34:00+0200")),
+                departure: Some(local_time("2024-04-23T11:40:00+0200")),
+                capacity1st: Some(1),
+                capacity2nd: Some(2),
+            }),
+            realtime_availability: Some(String::from("RT_BHF")),
+            location: Some(location_without_name("8503000")),
+        };
+        let test_data = r#"{
+            "station": {
+                "id": "8503000",
+                "name": null,
+                "score": null,
+                "coordinate": {
+                    "type": "WGS84",
+                    "x": null,
+                    "y": null
+                },
+                "distance": null
+            },
+            "arrival": "2024-04-23T11:32:00+0200",
+            "arrivalTimestamp": 1713864720,
+            "departure": "2024-04-23T11:38:00+0200",
+            "departureTimestamp": 1713865080,
+            "delay": 2,
+            "platform": "8",
+            "prognosis": {
+                "platform": "9",
+                "arrival": "2024-04-23T11:34:00+0200",
+                "departure": "2024-04-23T11:40:00+0200",
+                "capacity1st": 1,
+                "capacity2nd": 2
+            },
+            "realtimeAvailability": "RT_BHF",
+            "location": {
+                "id": "8503000",
+                "name": null,
+                "score": null,
+                "coordinate": {
+                    "type": "WGS84",
+                    "x": null,
+                    "y": null
+                },
+                "distance": null
+            }
+        }"#;
+
+        let s: Stop = serde_json::from_str(test_data).unwrap();
+        assert_eq!(s, expected);
+        assert_eq!(s.departure_time(), s.departure);
+        assert!(s.platform_changed());
+    }
+
+    #[test]
+    fn stop_times_from_timestamps() {
+        let test_data = r#"{
+            "station": {
+                "id": "8503000",
+                "name": null,
+                "score": null,
+                "coordinate": {
+                    "type": "WGS84",
+                    "x": null,
+                    "y": null
+                },
+                "distance": null
+            },
+            "arrival": null,
+            "arrivalTimestamp": 1713864720,
+            "departureTimestamp": 1713865080,
+            "delay": 0,
+            "platform": "8",
+            "prognosis": null
+        }"#;
+
+        let s: Stop = serde_json::from_str(test_data).unwrap();
+        assert_eq!(s.departure, None);
+        assert_eq!(s.location, None);
+        assert_eq!(
+            s.departure_time(),
+            Some(local_time("2024-04-23T11:38:00+0200"))
+        );
+
+        let s = Stop {
+            arrival_timestamp: None,
+            departure_timestamp: None,
+            ..s
+        };
+        assert_eq!(s.departure_time(), None);
+    }
+
+    #[test]
+    fn parse_journey_subcategory() {
+        let test_data = r#"{
+            "name": "000567",
+            "category": "IC",
+            "subcategory": "IC",
+            "categoryCode": "1",
+            "number": "3",
+            "operator": "SBB",
+            "to": "Chur",
+            "passList": [],
+            "capacity1st": 1,
+            "capacity2nd": 2
+        }"#;
+        let j: Journey = serde_json::from_str(test_data).unwrap();
+        assert_eq!(j.subcategory, Some(String::from("IC")));
+        assert_eq!(j.category_code, Some(String::from("1")));
+        assert_eq!(j.capacity1st, Some(1));
+        assert_eq!(j.capacity2nd, Some(2));
+    }
+
+    #[test]
+    fn parse_journey_nulls() {
+        let test_data = r#"{
+            "name": null,
+            "category": null,
+            "subcategory": null,
+            "categoryCode": null,
+            "number": null,
+            "operator": null,
+            "to": null,
+            "passList": [],
+            "capacity1st": null,
+            "capacity2nd": null
+        }"#;
+        let j: Journey = serde_json::from_str(test_data).unwrap();
+        assert_eq!(j.category, None);
+        assert_eq!(j.operator, None);
+        assert_eq!(j.to, None);
+
+        let c: Coordinate =
+            serde_json::from_str(r#"{"type": null, "x": null, "y": null}"#).unwrap();
+        assert_eq!(c.r#type, None);
+    }
+
+    #[test]
+    fn parse_journey() {
+        let expected = Journey {
+            name: Some(String::from("315188")),
+            category: Some(String::from("B")),
+            subcategory: None,
+            category_code: None,
+            number: Some(String::from("3")),
+            operator: Some(String::from("TPG")),
+            to: Some(String::from("Grand-Saconnex, Giacometti")),
+            capacity1st: None,
+            capacity2nd: None,
+            pass_list: vec![Stop {
+                station: location_without_name("8592899"),
+                arrival: None,
+                arrival_timestamp: None,
+                departure: Some(
+                    DateTime::parse_from_str("2024-04-19T12:09:00+0200", TIME_FORMAT)
+                        .unwrap()
+                        .with_timezone(&Local),
+                ),
+                departure_timestamp: Some(1713521340),
+                delay: Some(3),
+                platform: Some(String::from("F")),
+                prognosis: Some(prognosis_geneva()),
+                realtime_availability: None,
+                location: Some(location_without_name("8592899")),
+            }],
+        };
+        let test_data = r#"{
+            "stop": {
+                "station": {
+                    "id": "8587057",
+                    "name": "Gen\u00e8ve, gare Cornavin",
+                    "score": null,
+                    "coordinate": {
+                        "type": "WGS84",
+                        "x": 46.209751,
+                        "y": 6.14242
+                    },
+                    "distance": null
+                },
+                "arrival": null,
+                "arrivalTimestamp": null,
+                "departure": "2024-04-19T12:09:00+0200",
+                "departureTimestamp": 1713521340,
+                "delay": 3,
+                "platform": "F",
+                "prognosis": {
+                    "platform": null,
+                    "arrival": "2024-04-19T12:15:32+0200",
+                    "departure": "2024-04-19T12:12:00+0200",
+                    "capacity1st": null,
+                    "capacity2nd": null
+                },
+                "realtimeAvailability": null,
+                "location": {
+                    "id": "8592899",
+                    "name": null,
+                    "score": null,
+                    "coordinate": {
+                        "type": "WGS84",
+                        "x": null,
+                        "y": null
+                    },
+                    "distance": null
+                }
+            },
+            "name": "315188",
+            "category": "B",
+            "subcategory": null,
+            "categoryCode": null,
+            "number": "3",
+            "operator": "TPG",
+            "to": "Grand-Saconnex, Giacometti",
+            "passList": [
+                {
+                    "station": {
+                        "id": "8592899",
+                        "name": null,
+                        "score": null,
+                        "coordinate": {
+                            "type": "WGS84",
+                            "x": null,
+                            "y": null
+                        },
+                        "distance": null
+                    },
+                    "arrival": null,
+                    "arrivalTimestamp": null,
+                    "departure": "2024-04-19T12:09:00+0200",
+                    "departureTimestamp": 1713521340,
+                    "delay": 3,
+                    "platform": "F",
+                    "prognosis": {
+                        "platform": null,
+                        "arrival": "2024-04-19T12:15:32+0200",
+                        "departure": "2024-04-19T12:12:00+0200",
+                        "capacity1st": null,
+                        "capacity2nd": null
+                    },
+                    "realtimeAvailability": null,
+                    "location": {
+                        "id": "8592899",
+                        "name": null,
+                        "score": null,
+                        "coordinate": {
+                            "type": "WGS84",
+                            "x": null,
+                            "y": null
+                        },
+                        "distance": null
+                    }
+                }
+            ],
+            "capacity1st": null,
+            "capacity2nd": null
+        }"#;
+        let j: Journey = serde_json::from_str(test_data).unwrap();
+
+        assert_eq!(j, expected);
+
+        let expected = Journey {
+            name: Some(String::from("000567")),
+            category: Some(String::from("IC")),
+            subcategory: None,
+            category_code: None,
+            number: Some(String::from("3")),
+            operator: Some(String::from("SBB")),
+            to: Some(String::from("Chur")),
+            capacity1st: None,
+            capacity2nd: None,
+            pass_list: vec![Stop {
+                station: location_without_name("8509000"),
+                arrival: None,
+                arrival_timestamp: None,
+                departure: Some(
+                    DateTime::parse_from_str("2024-04-23T11:38:00+0200", TIME_FORMAT)
+                        .unwrap()
+                        .with_timezone(&Local),
+                ),
+                departure_timestamp: Some(1713865080),
+                delay: Some(0),
+                platform: Some(String::from("8")),
+                prognosis: Some(prognosis_zurich()),
+                realtime_availability: None,
+                location: Some(location_without_name("8509000")),
+            }],
+        };
+        let test_data = r#"{
+            "stop": {
+                "station": {
+                    "id": "8503000",
+                    "name": "Z\u00fcrich HB",
+                    "score": null,
+                    "coordinate": {
+                        "type": "WGS84",
+                        "x": 47.377847,
+                        "y": 8.540502
+                    },
+                    "distance": null
+                },
+                "arrival": null,
+                "arrivalTimestamp": null,
+                "departure": "2024-04-23T11:38:00+0200",
+                "departureTimestamp": 1713865080,
+                "delay": 0,
+                "platform": "8",
+                "prognosis": {
+                    "platform": null,
+                    "arrival": null,
+                    "departure": "2024-04-23T11:38:00+0200",
+                    "capacity1st": null,
+                    "capacity2nd": null
+                },
+                "realtimeAvailability": null,
+                "location": {
+                    "id": "8509000",
+                    "name": null,
+                    "score": null,
+                    "coordinate": {
+                        "type": "WGS84",
+                        "x": null,
+                        "y": null
+                    },
+                    "distance": null
+                }
+            },
+            "name": "000567",
+            "category": "IC",
+            "subcategory": null,
+            "categoryCode": null,
+            "number": "3",
+            "operator": "SBB",
+            "to": "Chur",
+            "passList": [
+                {
+                    "station": {
+                        "id": "8509000",
+                        "name": null,
+                        "score": null,
+                        "coordinate": {
+                            "type": "WGS84",
+                            "x": null,
+                            "y": null
+                        },
+                        "distance": null
+                    },
+                    "arrival": null,
+                    "arrivalTimestamp": null,
+                    "departure": "2024-04-23T11:38:00+0200",
+                    "departureTimestamp": 1713865080,
+                    "delay": 0,
+                    "platform": "8",
+                    "prognosis": {
+                        "platform": null,
+                        "arrival": null,
+                        "departure": "2024-04-23T11:38:00+0200",
+                        "capacity1st": null,
+                        "capacity2nd": null
+                    },
+                    "realtimeAvailability": null,
+                    "location": {
+                        "id": "8509000",
+                        "name": null,
+                        "score": null,
+                        "coordinate": {
+                            "type": "WGS84",
+                            "x": null,
+                            "y": null
+                        },
+                        "distance": null
+                    }
+                }],
+            "capacity1st": null,
+            "capacity2nd": null
+        }"#;
+        let j: Journey = serde_json::from_str(test_data).unwrap();
+
+        assert_eq!(j, expected)
+    }
+
+    #[test]
+    fn parse_stationboard() {
+        let file =
+            File::open(Path::new(env!("CARGO_MANIFEST_DIR")).join("geneve_cornavin_test.json"))
+                .unwrap();
+        let reader = BufReader::new(file);
+        let s: StationBoardResponse = serde_json::from_reader(reader).unwrap();
+        assert_eq!(s.station.coordinate, coordinate_geneva());
+        assert_eq!(s.station, location_geneva());
+        assert_eq!(
+            s.stationboard[0].clone().stop.prognosis.unwrap(),
+            prognosis_geneva()
+        );
+        assert_eq!(s.stationboard[0].clone().stop, stop_geneva());
+
+        let file =
+            File::open(Path::new(env!("CARGO_MANIFEST_DIR")).join("zurich_hb_test.json")).unwrap();
+        let reader = BufReader::new(file);
+        let s: StationBoardResponse = serde_json::from_reader(reader).unwrap();
+        assert_eq!(s.station.coordinate, coordinate_zurich());
+        assert_eq!(s.station, location_zurich());
+        assert_eq!(
+            s.stationboard[0].clone().stop.prognosis.unwrap(),
+            prognosis_zurich()
+        );
+        assert_eq!(s.stationboard[0].clone().stop, stop_zurich());
+    }
+
+    #[test]
+    fn parse_stationboard_skips_malformed_entries() {
+        let mut board: serde_json::Value = serde_json::from_reader(BufReader::new(
+            File::open(Path::new(env!("CARGO_MANIFEST_DIR")).join("geneve_cornavin_test.json"))
+                .unwrap(),
+        ))
+        .unwrap();
+        let entries = board["stationboard"].as_array_mut().unwrap();
+        let nb_entries = entries.len();
+        entries[0]["stop"] = serde_json::Value::from("not a stop");
+        entries[2]["passList"] = serde_json::Value::Null;
+        entries[3]["to"] = serde_json::Value::Null;
+
+        let s: StationBoardResponse = serde_json::from_value(board).unwrap();
+        assert_eq!(s.stationboard.len(), nb_entries - 2);
+        assert_eq!(
+            s.stationboard[0].journey.to,
+            Some(String::from("Veyrier, Tournettes"))
+        );
+        assert_eq!(s.stationboard[1].journey.to, None);
+        assert_eq!(s.stationboard[1].journey.number, Some(String::from("25")));
+    }
+
+    #[test]
+    fn parse_transport_modes_list() {
+        assert_eq!(
+            parse_transport_modes("bus, Tram,boat").unwrap(),
+            vec![TransportMode::Bus, TransportMode::Tram, TransportMode::Ship]
+        );
+        assert!(parse_transport_modes("bus,hovercraft").is_err());
+        for m in [
+            TransportMode::Train,
+            TransportMode::Tram,
+            TransportMode::Bus,
+            TransportMode::Ship,
+            TransportMode::Cableway,
+            TransportMode::Metro,
+        ] {
+            assert_eq!(TransportMode::from_str(&m.to_string()), Ok(m));
+        }
+    }
+
+    #[test]
+    fn transport_mode_from_category() {
+        assert_eq!(
+            TransportMode::from_category("IC"),
+            Some(TransportMode::Train)
+        );
+        assert_eq!(
+            TransportMode::from_category("S5"),
+            Some(TransportMode::Train)
+        );
+        assert_eq!(TransportMode::from_category("B"), Some(TransportMode::Bus));
+        assert_eq!(TransportMode::from_category("T"), Some(TransportMode::Tram));
+        assert_eq!(
+            TransportMode::from_category("BAT"),
+            Some(TransportMode::Ship)
+        );
+        assert_eq!(
+            TransportMode::from_category("FUN"),
+            Some(TransportMode::Cableway)
+        );
+        assert_eq!(
+            TransportMode::from_category("M"),
+            Some(TransportMode::Metro)
+        );
+        assert_eq!(TransportMode::from_category("XYZ"), None);
+        assert_eq!(
+            TransportMode::from_category_code("6"),
+            Some(TransportMode::Bus)
+        );
+        assert_eq!(
+            TransportMode::from_category_code("9"),
+            Some(TransportMode::Tram)
+        );
+        assert_eq!(TransportMode::from_category_code("10"), None);
+    }
+
+    #[test]
+    fn journey_transport_modes() {
+        let s = stationboard_geneva();
+        let modes: Vec<Option<TransportMode>> = s.stationboard[5..8]
+            .iter()
+            .map(|e| e.journey.transport_mode())
+            .collect();
+        assert_eq!(
+            modes,
+            vec![
+                Some(TransportMode::Bus),
+                Some(TransportMode::Bus),
+                Some(TransportMode::Tram)
+            ]
+        );
+
+        let s = stationboard_zurich();
+        assert!(s
+            .stationboard
+            .iter()
+            .all(|e| e.journey.transport_mode() == Some(TransportMode::Train)));
+
+        let j = Journey {
+            category: Some(String::from("unknown")),
+            category_code: Some(String::from("4")),
+            ..s.stationboard[0].journey.clone()
+        };
+        assert_eq!(j.transport_mode(), Some(TransportMode::Ship));
+    }
+
+    #[test]
+    fn parse_board_types_list() {
+        assert_eq!(
+            parse_board_types("arrival").unwrap(),
+            vec![BoardType::Arrival]
+        );
+        assert_eq!(
+            parse_board_types("Both").unwrap(),
+            vec![BoardType::Departure, BoardType::Arrival]
+        );
+        assert_eq!(
+            parse_board_types("arrival, departure").unwrap(),
+            vec![BoardType::Arrival, BoardType::Departure]
+        );
+        assert!(parse_board_types("arrivals").is_err());
+    }
+
+    #[test]
+    fn departure_element() {
+        let s = stationboard_geneva();
+        let e = &s.stationboard[0];
+        assert_eq!(e.board_type, BoardType::Departure);
+        assert_eq!(e.time(), Some(local_time("2024-04-19T12:09:00+0200")));
+        assert_eq!(e.delay(), Some(3));
+        assert!(e.has_realtime());
+        assert_eq!(
+            e.destination_or_origin(&s.station),
+            Some("Grand-Saconnex, Giacometti")
+        );
+    }
+
+    #[test]
+    fn arrival_element() {
+        let s = stationboard_geneva();
+        let mut e = s.stationboard[0].clone();
+        e.board_type = BoardType::Arrival;
+        // the arrival is missing, as on departure boards
+        assert_eq!(e.time(), None);
+
+        e.stop.arrival = Some(local_time("2024-04-19T12:10:00+0200"));
+        assert_eq!(e.time(), Some(local_time("2024-04-19T12:10:00+0200")));
+        // prognosis arrival at 12:15:32
+        assert_eq!(e.delay(), Some(5));
+        assert!(e.has_realtime());
+        // a prognosis of the departure only does not tell the arrival delay
+        assert_eq!(e.stop.delay, Some(3));
+        if let Some(p) = e.stop.prognosis.as_mut() {
+            p.arrival = None;
+        }
+        assert_eq!(e.delay(), None);
+        assert!(!e.has_realtime());
+        e.stop.prognosis = None;
+        assert_eq!(e.delay(), None);
+        assert!(!e.has_realtime());
+
+        // the origin is the first named stop of the pass list, the unnamed
+        // 8592899 one being skipped
+        assert_eq!(e.destination_or_origin(&s.station), Some("Genève, Lyon"));
+        // as well as the board station
+        e.journey.pass_list = s.stationboard[0].journey.pass_list[1..].to_vec();
+        e.journey.pass_list[0].station = s.station.clone();
+        assert_eq!(e.destination_or_origin(&s.station), Some("Genève, Poterie"));
+        // falling back on the destination without a pass list
+        e.journey.pass_list.clear();
+        assert_eq!(
+            e.destination_or_origin(&s.station),
+            Some("Grand-Saconnex, Giacometti")
+        );
+    }
+}
@@ -0,0 +1,1105 @@
+//! Drawing of the board lines into a 1-bit frame, ready to be pushed to the
+//! e-paper display or saved as an image, or into the black and accent planes
+//! of tri-color panels.
+
+use crate::badges::{Badge, BadgeColor, Shape};
+use crate::format::{truncate_words, Alert, Header, LineInfo};
+use crate::icons::icon;
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+// resolution of the 4.2 inch display
+pub const DISPLAY_WIDTH: u32 = 400;
+pub const DISPLAY_HEIGHT: u32 = 300;
+pub const DEFAULT_FONT_FILE: &str = "../display_controller/pic/Menlo.ttc";
+// minimum coverage of a pixel by a glyph for it to be drawn black
+const COVERAGE_THRESHOLD: f32 = 0.5;
+// unchanged rows between changes that are still refreshed together
+const REGION_GAP: u32 = 8;
+// prefixes the update time in the header when no line has realtime data
+pub const TIMETABLE_MARKER: &str = "~";
+// prefixes the stations of a merged board which could not be fetched
+pub const FAILURE_MARKER: &str = "⚠";
+
+/// Rectangle of pixels, from `(x0, y0)` included to `(x1, y1)` excluded.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Region {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl Region {
+    pub fn area(&self) -> u32 {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
+}
+
+/// Black and white pixels of the display.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    /// Row by row, `true` being black.
+    pixels: Vec<bool>,
+}
+
+impl Frame {
+    /// A white frame.
+    pub fn new(width: u32, height: u32) -> Self {
+        Frame {
+            width,
+            height,
+            pixels: vec![false; (width * height) as usize],
+        }
+    }
+
+    /// Whether the pixel is black, pixels out of the frame being white.
+    pub fn get(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some_and(|i| self.pixels[i])
+    }
+
+    /// Sets a pixel, ignoring the ones out of the frame.
+    pub fn set(&mut self, x: i32, y: i32, black: bool) {
+        if let Some(i) = self.index(x, y) {
+            self.pixels[i] = black;
+        }
+    }
+
+    /// Fills the rectangle between both corners, included.
+    pub fn fill_rect(&mut self, (x0, y0): (i32, i32), (x1, y1): (i32, i32), black: bool) {
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                self.set(x, y, black);
+            }
+        }
+    }
+
+    /// Fills the rectangle between both corners, included, its corners rounded
+    /// to at most `radius`.
+    pub fn fill_rounded_rect(
+        &mut self,
+        (x0, y0): (i32, i32),
+        (x1, y1): (i32, i32),
+        radius: u32,
+        black: bool,
+    ) {
+        let r = (radius as f32)
+            .min((x1 - x0) as f32 / 2.0)
+            .min((y1 - y0) as f32 / 2.0);
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                // distance to the center of the nearest corner
+                let cx = (x as f32).clamp(x0 as f32 + r, x1 as f32 - r);
+                let cy = (y as f32).clamp(y0 as f32 + r, y1 as f32 - r);
+                if (x as f32 - cx).powi(2) + (y as f32 - cy).powi(2) <= r * r + r / 2.0 {
+                    self.set(x, y, black);
+                }
+            }
+        }
+    }
+
+    /// Draws the black pixels of `other` with its top left corner at `(x, y)`.
+    pub fn overlay(&mut self, other: &Frame, (x, y): (i32, i32)) {
+        for oy in 0..other.height as i32 {
+            for ox in 0..other.width as i32 {
+                if other.get(ox, oy) {
+                    self.set(x + ox, y + oy, true);
+                }
+            }
+        }
+    }
+
+    /// Number of pixels differing from `other`, all of them when the sizes
+    /// differ.
+    pub fn diff_count(&self, other: &Frame) -> usize {
+        if (self.width, self.height) != (other.width, other.height) {
+            return self.pixels.len().max(other.pixels.len());
+        }
+        self.pixels
+            .iter()
+            .zip(&other.pixels)
+            .filter(|(a, b)| a != b)
+            .count()
+    }
+
+    /// Regions where the frame differs from `previous`, one per band of changed
+    /// rows, the whole frame when their sizes differ.
+    pub fn changed_regions(&self, previous: &Frame) -> Vec<Region> {
+        if (self.width, self.height) != (previous.width, previous.height) {
+            return vec![Region {
+                x0: 0,
+                y0: 0,
+                x1: self.width,
+                y1: self.height,
+            }];
+        }
+        let mut regions: Vec<Region> = Vec::new();
+        for y in 0..self.height {
+            let row = (y * self.width) as usize..((y + 1) * self.width) as usize;
+            let changed = self.pixels[row.clone()]
+                .iter()
+                .zip(&previous.pixels[row])
+                .enumerate()
+                .filter(|(_, (a, b))| a != b)
+                .map(|(x, _)| x as u32);
+            let Some((x0, x1)) = changed.fold(None, |r, x| match r {
+                None => Some((x, x + 1)),
+                Some((x0, _)) => Some((x0, x + 1)),
+            }) else {
+                continue;
+            };
+            match regions.last_mut() {
+                Some(r) if y <= r.y1 + REGION_GAP => {
+                    r.x0 = r.x0.min(x0);
+                    r.x1 = r.x1.max(x1);
+                    r.y1 = y + 1;
+                }
+                _ => regions.push(Region {
+                    x0,
+                    y0: y,
+                    x1,
+                    y1: y + 1,
+                }),
+            }
+        }
+        regions
+    }
+
+    /// Grayscale image of the frame.
+    pub fn to_image(&self) -> image::GrayImage {
+        image::GrayImage::from_fn(self.width, self.height, |x, y| {
+            image::Luma([if self.get(x as i32, y as i32) { 0 } else { 255 }])
+        })
+    }
+
+    /// Frame of an image, its dark pixels being black.
+    pub fn from_image(image: &image::GrayImage) -> Self {
+        let mut frame = Frame::new(image.width(), image.height());
+        for (x, y, p) in image.enumerate_pixels() {
+            frame.set(x as i32, y as i32, p.0[0] < 128);
+        }
+        frame
+    }
+
+    /// Saves the frame as an image, in the format of the `path` extension,
+    /// e.g. bmp or png.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        self.to_image()
+            .save(path)
+            .map_err(|e| format!("could not save {}: {}", path.display(), e))
+    }
+
+    /// Reads a frame from an image file.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let image =
+            image::open(path).map_err(|e| format!("could not open {}: {}", path.display(), e))?;
+        Ok(Frame::from_image(&image.to_luma8()))
+    }
+
+    /// Frame turned clockwise by `quarter_turns` quarters of a turn.
+    pub fn rotate(&self, quarter_turns: u32) -> Self {
+        let (width, height) = match quarter_turns % 2 {
+            0 => (self.width, self.height),
+            _ => (self.height, self.width),
+        };
+        let (w, h) = (self.width as i32, self.height as i32);
+        let mut rotated = Frame::new(width, height);
+        for y in 0..h {
+            for x in 0..w {
+                let (rx, ry) = match quarter_turns % 4 {
+                    0 => (x, y),
+                    1 => (h - 1 - y, x),
+                    2 => (w - 1 - x, h - 1 - y),
+                    _ => (y, w - 1 - x),
+                };
+                rotated.set(rx, ry, self.get(x, y));
+            }
+        }
+        rotated
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+        Some(y as usize * self.width as usize + x as usize)
+    }
+}
+
+/// Pixels of a tri-color panel, one frame per color, white where neither is
+/// set.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Planes {
+    pub black: Frame,
+    /// Pixels of the second color of the panel, e.g. red.
+    pub accent: Frame,
+}
+
+impl Planes {
+    /// White planes.
+    pub fn new(width: u32, height: u32) -> Self {
+        Planes {
+            black: Frame::new(width, height),
+            accent: Frame::new(width, height),
+        }
+    }
+
+    /// The accent plane if `accent`, else the black one.
+    pub fn plane(&mut self, accent: bool) -> &mut Frame {
+        if accent {
+            &mut self.accent
+        } else {
+            &mut self.black
+        }
+    }
+
+    /// Both planes in a single frame, the accent being black, for black and
+    /// white panels.
+    pub fn merged(&self) -> Frame {
+        let mut merged = self.black.clone();
+        for (m, a) in merged.pixels.iter_mut().zip(&self.accent.pixels) {
+            *m |= a;
+        }
+        merged
+    }
+
+    /// Planes turned clockwise by `quarter_turns` quarters of a turn.
+    pub fn rotate(&self, quarter_turns: u32) -> Self {
+        Planes {
+            black: self.black.rotate(quarter_turns),
+            accent: self.accent.rotate(quarter_turns),
+        }
+    }
+}
+
+/// Image file of the accent plane saved along the black one at `path`, e.g.
+/// `board_accent.png` for `board.png`.
+pub fn accent_plane_file(path: &Path) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push("_accent");
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    path.with_file_name(name)
+}
+
+/// Sizes of the board rows, in pixels.
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Style {
+    /// Em size of the font.
+    pub font_size: f32,
+    /// Distance between the tops of two rows.
+    pub row_height: u32,
+    /// Distance between the top of a row and its separator.
+    pub separator_offset: u32,
+    /// Thickness of the separators, none being drawn when 0.
+    pub separator_thickness: u32,
+    /// Space left on each side of the separators.
+    pub separator_margin: u32,
+    /// Width of the line number column, the destination starting after it,
+    /// widened to the widest number of the board.
+    pub line_width: u32,
+    /// Width of the delay column, the time being right aligned before it.
+    pub delay_width: u32,
+    /// Minimum space between the line number, destination and time.
+    pub column_gap: u32,
+    /// Em size of the font of the header.
+    pub header_font_size: f32,
+    /// Size of the transport mode icons standing on the baseline before the
+    /// line numbers, none being drawn when 0.
+    pub icon_size: u32,
+}
+
+impl Default for Style {
+    /// Rows of the original display, 5 of them fitting the 4.2 inch one, with
+    /// columns where its monospace layout put them.
+    fn default() -> Self {
+        Style {
+            font_size: 31.0,
+            row_height: 65,
+            separator_offset: 47,
+            separator_thickness: 3,
+            separator_margin: 20,
+            line_width: 76,
+            delay_width: 58,
+            column_gap: 19,
+            header_font_size: 20.0,
+            icon_size: 0,
+        }
+    }
+}
+
+/// Draws the board lines with a font and a style.
+pub struct Renderer {
+    font: FontVec,
+    scale: PxScale,
+    header_scale: PxScale,
+    pub style: Style,
+}
+
+impl Renderer {
+    /// Renderer of the font `data`, the font at `index` for collections.
+    pub fn new(data: Vec<u8>, index: u32, style: Style) -> Result<Self, String> {
+        let font = FontVec::try_from_vec_and_index(data, index)
+            .map_err(|e| format!("invalid font: {}", e))?;
+        // ab_glyph scales fonts by their height rather than their em size
+        let units_per_em = font
+            .units_per_em()
+            .ok_or(String::from("font without units per em"))?;
+        let scale = |size: f32| PxScale::from(size * font.height_unscaled() / units_per_em);
+        Ok(Renderer {
+            scale: scale(style.font_size),
+            header_scale: scale(style.header_font_size),
+            font,
+            style,
+        })
+    }
+
+    /// Renderer of the first font of the file at `path`.
+    pub fn from_file(path: &Path, style: Style) -> Result<Self, String> {
+        let data = std::fs::read(path)
+            .map_err(|e| format!("could not read font {}: {}", path.display(), e))?;
+        Self::new(data, 0, style)
+    }
+
+    /// Draws `text` with its top left corner at `(x, y)`, returning the
+    /// abscissa it ends at.
+    pub fn draw_text(&self, frame: &mut Frame, x: f32, y: f32, text: &str) -> f32 {
+        self.draw_scaled_text(frame, self.scale, (x, y), text, true)
+    }
+
+    /// Draws `text` at `scale` in black, or white on black backgrounds.
+    fn draw_scaled_text(
+        &self,
+        frame: &mut Frame,
+        scale: PxScale,
+        (x, y): (f32, f32),
+        text: &str,
+        black: bool,
+    ) -> f32 {
+        let font = self.font.as_scaled(scale);
+        let baseline = (y + font.ascent()).round();
+        let mut caret = x.round();
+        for c in text.chars() {
+            let mut glyph = font.scaled_glyph(c);
+            glyph.position = point(caret, baseline);
+            // whole pixel advances as hinted fonts, keeping glyphs sharp
+            caret += font.h_advance(glyph.id).round();
+            let Some(outline) = self.font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            outline.draw(|gx, gy, coverage| {
+                if coverage >= COVERAGE_THRESHOLD {
+                    frame.set(
+                        bounds.min.x as i32 + gx as i32,
+                        bounds.min.y as i32 + gy as i32,
+                        black,
+                    );
+                }
+            });
+        }
+        caret
+    }
+
+    /// Width of `text` once drawn.
+    pub fn text_width(&self, text: &str) -> f32 {
+        self.scaled_text_width(self.scale, text)
+    }
+
+    fn scaled_text_width(&self, scale: PxScale, text: &str) -> f32 {
+        let font = self.font.as_scaled(scale);
+        text.chars()
+            .map(|c| font.h_advance(font.glyph_id(c)).round())
+            .sum()
+    }
+
+    /// Frame of the board lines, one per row each followed by a separator.
+    pub fn render(&self, lines: &[LineInfo], width: u32, height: u32) -> Frame {
+        let mut planes = Planes::new(width, height);
+        self.draw_rows(&mut planes, 0, lines);
+        planes.merged()
+    }
+
+    /// Draws the header in the first `height` pixels: the station on the left
+    /// and the update time on the right, prefixed by [`TIMETABLE_MARKER`]
+    /// without realtime data and inverted when stale, the stations which could
+    /// not be fetched following [`FAILURE_MARKER`] before it. Its separator is
+    /// where the one of a row ending at `height` would be.
+    pub fn draw_header(&self, frame: &mut Frame, height: u32, header: &Header) {
+        let s = &self.style;
+        let separator = height as i32 - s.row_height as i32 + s.separator_offset as i32;
+        if s.separator_thickness > 0 {
+            frame.fill_rect(
+                (0, separator),
+                (
+                    frame.width as i32 - 1,
+                    separator + s.separator_thickness as i32 - 1,
+                ),
+                true,
+            );
+        }
+        // texts centered above the separator
+        let font = self.font.as_scaled(self.header_scale);
+        let top = ((separator as f32 - font.height()) / 2.0).max(0.0);
+        let marker = if header.timetable_only {
+            TIMETABLE_MARKER
+        } else {
+            ""
+        };
+        let time = format!("{}{}", marker, header.updated);
+        let padding = (s.column_gap / 2) as f32;
+        let time_left =
+            frame.width as f32 - padding - self.scaled_text_width(self.header_scale, &time);
+        if header.stale {
+            frame.fill_rect(
+                ((time_left - padding) as i32, 0),
+                (frame.width as i32 - 1, separator - 1),
+                true,
+            );
+        }
+        self.draw_scaled_text(
+            frame,
+            self.header_scale,
+            (time_left, top),
+            &time,
+            !header.stale,
+        );
+        let width = |t: &str| self.scaled_text_width(self.header_scale, t).ceil() as usize;
+        let mut station_right = time_left - padding;
+        if !header.failed.is_empty() {
+            let failed = truncate_words(
+                &format!("{} {}", FAILURE_MARKER, header.failed.join(" ")),
+                (station_right - s.column_gap as f32).max(0.0) as usize,
+                width,
+            );
+            station_right -= s.column_gap as f32 + width(&failed) as f32;
+            self.draw_scaled_text(
+                frame,
+                self.header_scale,
+                (station_right, top),
+                &failed,
+                true,
+            );
+        }
+        let station = truncate_words(
+            &header.station,
+            (station_right - s.column_gap as f32).max(0.0) as usize,
+            width,
+        );
+        self.draw_scaled_text(frame, self.header_scale, (0.0, top), &station, true);
+    }
+
+    /// Draws the board lines from `top`, one per row each followed by a
+    /// separator, their alerts and accent badges on the accent plane.
+    pub fn draw_rows(&self, planes: &mut Planes, top: u32, lines: &[LineInfo]) {
+        let s = &self.style;
+        let destination_left = self.destination_left(planes.black.width, lines);
+        for (i, l) in lines.iter().enumerate() {
+            let row_top = (top + i as u32 * s.row_height) as f32;
+            self.draw_row(planes, row_top, destination_left, l);
+            if s.separator_thickness > 0 {
+                let y = row_top as i32 + s.separator_offset as i32;
+                let frame = &mut planes.black;
+                frame.fill_rect(
+                    (s.separator_margin as i32, y),
+                    (
+                        frame.width.saturating_sub(s.separator_margin) as i32,
+                        y + s.separator_thickness as i32 - 1,
+                    ),
+                    true,
+                );
+            }
+        }
+    }
+
+    /// Left of the destinations: after the line number column, widened so
+    /// that the widest number of `lines` fits, up to half the `width` of the
+    /// frame.
+    fn destination_left(&self, width: u32, lines: &[LineInfo]) -> f32 {
+        let s = &self.style;
+        let widest = lines
+            .iter()
+            .map(|l| self.number_width(&l.line_number, l.badge))
+            .fold(0.0, f32::max);
+        let needed = (self.number_left() + widest + s.column_gap as f32).ceil();
+        needed.min((width / 2) as f32).max(s.line_width as f32)
+    }
+
+    /// Left of the line numbers, aligned whether their line has an icon or
+    /// not.
+    fn number_left(&self) -> f32 {
+        match self.style.icon_size {
+            0 => 0.0,
+            size => (size + self.style.column_gap / 2) as f32,
+        }
+    }
+
+    /// Width of `number` once drawn, in its badge if any.
+    fn number_width(&self, number: &str, badge: Option<Badge>) -> f32 {
+        let text_width = self.text_width(number);
+        match badge {
+            Some(b) => {
+                let (padding, height) = self.badge_box();
+                let width = text_width + 2.0 * padding as f32;
+                match b.shape {
+                    Shape::Circle => width.max(height as f32),
+                    _ => width,
+                }
+            }
+            None => text_width,
+        }
+    }
+
+    /// Draws the columns of a line from `top`: the icon of its mode, the line
+    /// number in its badge, the destination from `destination_left` up to the
+    /// right aligned time, and the delay after it. Texts too wide for their
+    /// column are truncated.
+    fn draw_row(&self, planes: &mut Planes, top: f32, destination_left: f32, l: &LineInfo) {
+        let s = &self.style;
+        let cancelled = l.alerts.contains(&Alert::Cancellation);
+        let accent = |alert| cancelled || l.alerts.contains(&alert);
+        let delay_left = planes.black.width.saturating_sub(s.delay_width) as f32;
+        let time_left = delay_left - self.text_width(&l.normal_time);
+        let number_left = self.number_left();
+        if let Some(mode) = l.mode.filter(|_| s.icon_size > 0) {
+            let baseline = top + self.font.as_scaled(self.scale).ascent();
+            planes.plane(cancelled).overlay(
+                &icon(mode, s.icon_size),
+                (0, baseline.round() as i32 - s.icon_size as i32),
+            );
+        }
+        let number_width = destination_left - s.column_gap as f32 - number_left;
+        match l.badge {
+            Some(b) => self.draw_badge(
+                planes.plane(cancelled || b.color == BadgeColor::Accent),
+                (number_left, top),
+                number_width,
+                &l.line_number,
+                b,
+            ),
+            None => {
+                self.draw_text(
+                    planes.plane(cancelled),
+                    number_left,
+                    top,
+                    &self.truncate(&l.line_number, number_width),
+                );
+            }
+        }
+        self.draw_text(
+            planes.plane(accent(Alert::PlatformChange)),
+            destination_left,
+            top,
+            &self.truncate(
+                &l.direction,
+                time_left - s.column_gap as f32 - destination_left,
+            ),
+        );
+        self.draw_text(planes.plane(cancelled), time_left, top, &l.normal_time);
+        self.draw_text(
+            planes.plane(accent(Alert::Delay)),
+            delay_left,
+            top,
+            &self.truncate(&delay_text(l.delay), s.delay_width as f32),
+        );
+    }
+
+    /// Padding of the badges and their height, spanning from the top of the
+    /// digits to the baseline plus the padding.
+    fn badge_box(&self) -> (i32, i32) {
+        let font = self.font.as_scaled(self.scale);
+        let mut digit = font.scaled_glyph('0');
+        digit.position = point(0.0, 0.0);
+        let digit_height = match self.font.outline_glyph(digit) {
+            Some(o) => -o.px_bounds().min.y.round() as i32,
+            None => font.ascent().round() as i32,
+        };
+        let padding = (self.style.font_size / 6.0).round().max(2.0) as i32;
+        (padding, digit_height + 2 * padding + 1)
+    }
+
+    /// Draws `number` in `badge` from `(left, top)`, in at most `max` pixels
+    /// unless a circle. Outlined badges are as thick as half their padding.
+    fn draw_badge(
+        &self,
+        frame: &mut Frame,
+        (left, top): (f32, f32),
+        max: f32,
+        number: &str,
+        badge: Badge,
+    ) {
+        let font = self.font.as_scaled(self.scale);
+        let baseline = (top + font.ascent()).round() as i32;
+        let (padding, height) = self.badge_box();
+        let number = self.truncate(number, max - 2.0 * padding as f32);
+        let text_width = self.text_width(&number).round() as i32;
+        let (y0, y1) = (baseline + padding + 1 - height, baseline + padding);
+        let width = match badge.shape {
+            Shape::Circle => (text_width + 2 * padding).max(height),
+            _ => text_width + 2 * padding,
+        };
+        let (x0, x1) = (left.round() as i32, left.round() as i32 + width - 1);
+        let radius = match badge.shape {
+            Shape::Rectangle => 0,
+            Shape::Rounded => height / 4,
+            Shape::Circle => height / 2,
+        };
+        frame.fill_rounded_rect((x0, y0), (x1, y1), radius as u32, true);
+        if !badge.inverted {
+            let t = (padding / 2).max(1);
+            frame.fill_rounded_rect(
+                (x0 + t, y0 + t),
+                (x1 - t, y1 - t),
+                (radius - t).max(0) as u32,
+                false,
+            );
+        }
+        self.draw_scaled_text(
+            frame,
+            self.scale,
+            ((x0 + (width - text_width) / 2) as f32, top),
+            &number,
+            !badge.inverted,
+        );
+    }
+
+    /// `text` truncated to fit in `max` pixels, see [`truncate_words`].
+    pub fn truncate(&self, text: &str, max: f32) -> String {
+        truncate_words(text, max.max(0.0) as usize, |t| {
+            self.text_width(t).ceil() as usize
+        })
+    }
+}
+
+/// Displayed delay, empty when on time.
+pub fn delay_text(delay: i32) -> String {
+    match delay {
+        0 => String::new(),
+        d if d > 0 => format!("+{}", d),
+        d => d.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::TransportMode;
+    use std::path::PathBuf;
+
+    fn manifest_path(file: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(file)
+    }
+
+    fn renderer() -> Renderer {
+        Renderer::from_file(&manifest_path(DEFAULT_FONT_FILE), Style::default()).unwrap()
+    }
+
+    /// Lines of the result file used to generate `test_display.bmp`, their
+    /// directions shortened as the display controller does.
+    fn display_controller_lines() -> Vec<LineInfo> {
+        std::fs::read_to_string(manifest_path("api_result_test.tsv"))
+            .unwrap()
+            .lines()
+            .map(|l| {
+                let mut l: LineInfo = l.parse().unwrap();
+                let chars: Vec<char> = l.direction.chars().collect();
+                if chars.len() > 8 {
+                    l.direction = chars[..3].iter().chain(&['.', '.']).collect::<String>()
+                        + &chars[chars.len() - 3..].iter().collect::<String>();
+                }
+                l
+            })
+            .collect()
+    }
+
+    fn line(line_number: &str, direction: &str, delay: i32) -> LineInfo {
+        LineInfo {
+            line_number: line_number.to_owned(),
+            direction: direction.to_owned(),
+            normal_time: String::from("10:46"),
+            delay,
+            mode: None,
+            badge: None,
+            alerts: Vec::new(),
+        }
+    }
+
+    // bottom of the text of the first row, above its separator
+    const TEXT_BOTTOM: i32 = 47;
+
+    /// Whether the texts of both frames are the same between the columns `left`
+    /// and `right`.
+    fn same_columns(a: &Frame, b: &Frame, left: i32, right: i32) -> bool {
+        (0..TEXT_BOTTOM).all(|y| (left..right).all(|x| a.get(x, y) == b.get(x, y)))
+    }
+
+    /// Whether the text of the frame has black pixels between the columns
+    /// `left` and `right`.
+    fn has_black(frame: &Frame, left: i32, right: i32) -> bool {
+        (0..TEXT_BOTTOM).any(|y| (left..right).any(|x| frame.get(x, y)))
+    }
+
+    #[test]
+    fn delay_texts() {
+        assert_eq!(delay_text(0), "");
+        assert_eq!(delay_text(3), "+3");
+        assert_eq!(delay_text(-1), "-1");
+    }
+
+    #[test]
+    fn columns_alignment() {
+        let r = renderer();
+        let s = &r.style;
+        let render = |l: LineInfo| r.render(&[l], DISPLAY_WIDTH, DISPLAY_HEIGHT);
+        let width = DISPLAY_WIDTH as i32;
+        let destination_left = s.line_width as i32;
+        let delay_left = width - s.delay_width as i32;
+        let time_left = delay_left - r.text_width("10:46") as i32;
+
+        let short = render(line("6", "Plage", 0));
+        // a long line number widens its column rather than being truncated
+        let long_number = render(line("IR15", "Plage", 0));
+        let number_right = r.text_width("IR15") as i32;
+        assert!(number_right > destination_left - s.column_gap as i32);
+        let mut full_number = Frame::new(DISPLAY_WIDTH, DISPLAY_HEIGHT);
+        r.draw_text(&mut full_number, 0.0, 0.0, "IR15");
+        assert!(same_columns(&long_number, &full_number, 0, number_right));
+        assert!(!same_columns(&short, &long_number, destination_left, width));
+        // the destinations of the other rows moving along
+        let mixed = r.render(
+            &[line("6", "Plage", 0), line("IR15", "Plage", 0)],
+            DISPLAY_WIDTH,
+            DISPLAY_HEIGHT,
+        );
+        assert!(same_columns(&mixed, &long_number, number_right, width));
+        // so is a long destination, the time being right aligned
+        let long_destination = render(line("6", "Genève-Aéroport", 12));
+        assert!(same_columns(
+            &short,
+            &long_destination,
+            time_left,
+            delay_left
+        ));
+        assert!(!has_black(
+            &long_destination,
+            time_left - s.column_gap as i32,
+            time_left
+        ));
+        assert!(!has_black(&short, delay_left, width));
+        assert!(has_black(&long_destination, delay_left, width));
+    }
+
+    #[test]
+    fn mode_icons() {
+        let mut r = renderer();
+        let tram = LineInfo {
+            mode: Some(TransportMode::Tram),
+            ..line("12", "Plage", 0)
+        };
+        // no icon without a size
+        let render = |r: &Renderer, l: LineInfo| r.render(&[l], DISPLAY_WIDTH, DISPLAY_HEIGHT);
+        assert_eq!(render(&r, tram.clone()), render(&r, line("12", "Plage", 0)));
+
+        r.style.icon_size = 24;
+        let with_icon = render(&r, tram);
+        let without_icon = render(&r, line("12", "Plage", 0));
+        // the icon stands on the baseline before the line number
+        let baseline = r.font.as_scaled(r.scale).ascent().round() as i32;
+        let mut icon_frame = Frame::new(DISPLAY_WIDTH, DISPLAY_HEIGHT);
+        icon_frame.overlay(&icon(TransportMode::Tram, 24), (0, baseline - 24));
+        assert!(same_columns(&with_icon, &icon_frame, 0, 24));
+        assert!(!has_black(&without_icon, 0, 24));
+        // which is shifted the same whether its line has an icon or not
+        let number_left = 24 + r.style.column_gap as i32 / 2;
+        assert!(same_columns(
+            &with_icon,
+            &without_icon,
+            24,
+            DISPLAY_WIDTH as i32
+        ));
+        assert!(has_black(&without_icon, number_left, number_left + 10));
+    }
+
+    #[test]
+    fn number_badges() {
+        let r = renderer();
+        let render = |badge| {
+            let l = LineInfo {
+                badge,
+                ..line("12", "Plage", 0)
+            };
+            r.render(&[l], DISPLAY_WIDTH, DISPLAY_HEIGHT)
+        };
+        let plain = render(None);
+        let destination_left = r.style.line_width as i32;
+        let black = |f: &Frame| {
+            (0..TEXT_BOTTOM)
+                .flat_map(|y| (0..destination_left).map(move |x| (x, y)))
+                .filter(|&(x, y)| f.get(x, y))
+                .count()
+        };
+        let badge = |shape, inverted| {
+            Some(Badge {
+                shape,
+                inverted,
+                color: BadgeColor::Black,
+            })
+        };
+        // a filled badge is mostly black, an outlined one mostly white
+        let inverted = render(badge(Shape::Rectangle, true));
+        let outlined = render(badge(Shape::Rectangle, false));
+        assert!(black(&inverted) > 2 * black(&outlined));
+        assert!(black(&outlined) > black(&plain));
+        // the other columns do not move
+        assert!(same_columns(
+            &plain,
+            &inverted,
+            destination_left,
+            DISPLAY_WIDTH as i32
+        ));
+        // rounded corners
+        let rounded = render(badge(Shape::Rounded, true));
+        assert!(black(&rounded) < black(&inverted));
+        let first_black = |f: &Frame| (0..TEXT_BOTTOM).find(|&y| f.get(0, y));
+        assert!(first_black(&rounded) > first_black(&inverted));
+        // and the whole badge stays in its column, before the destination
+        for b in [
+            inverted,
+            outlined,
+            rounded,
+            render(badge(Shape::Circle, true)),
+        ] {
+            assert!(!has_black(
+                &b,
+                destination_left - r.style.column_gap as i32,
+                destination_left
+            ));
+        }
+    }
+
+    #[test]
+    fn accent_planes() {
+        let r = renderer();
+        let draw = |l: LineInfo| {
+            let mut planes = Planes::new(DISPLAY_WIDTH, DISPLAY_HEIGHT);
+            r.draw_rows(&mut planes, 0, &[l]);
+            planes
+        };
+        let alerted = |alerts| LineInfo {
+            alerts,
+            ..line("6", "Plage", 3)
+        };
+        let width = DISPLAY_WIDTH as i32;
+        let destination_left = r.style.line_width as i32;
+        let delay_left = width - r.style.delay_width as i32;
+        let plain = draw(line("6", "Plage", 3));
+        assert_eq!(plain.accent, Frame::new(DISPLAY_WIDTH, DISPLAY_HEIGHT));
+
+        // the alerts only move parts of the row to the accent plane
+        let delayed = draw(alerted(vec![Alert::Delay]));
+        assert_eq!(delayed.merged(), plain.merged());
+        assert!(has_black(&delayed.accent, delay_left, width));
+        assert!(!has_black(&delayed.accent, 0, delay_left));
+        assert!(!has_black(&delayed.black, delay_left, width));
+
+        let moved = draw(alerted(vec![Alert::PlatformChange]));
+        assert_eq!(moved.merged(), plain.merged());
+        assert!(same_columns(
+            &moved.accent,
+            &plain.black,
+            destination_left,
+            destination_left + 50
+        ));
+        assert!(!has_black(&moved.accent, 0, destination_left));
+
+        // the whole row of a cancelled line, but not its separator
+        let cancelled = draw(alerted(vec![Alert::Cancellation]));
+        assert_eq!(cancelled.merged(), plain.merged());
+        assert!(!has_black(&cancelled.black, 0, width));
+        assert!(cancelled.black.get(200, TEXT_BOTTOM));
+
+        // as well as the accent badges
+        let badged = draw(LineInfo {
+            badge: Some(Badge {
+                shape: Shape::Rounded,
+                inverted: true,
+                color: BadgeColor::Accent,
+            }),
+            ..line("6", "Plage", 3)
+        });
+        assert!(has_black(&badged.accent, 0, destination_left));
+        assert!(!has_black(&badged.black, 0, destination_left));
+        assert!(has_black(&badged.black, destination_left, width));
+
+        assert_eq!(
+            accent_plane_file(Path::new("/tmp/board.png")),
+            Path::new("/tmp/board_accent.png")
+        );
+        assert_eq!(
+            accent_plane_file(Path::new("board")),
+            Path::new("board_accent")
+        );
+    }
+
+    #[test]
+    fn truncate_to_pixels() {
+        let r = renderer();
+        let t = r.truncate("Genève-Aéroport", 100.0);
+        assert!(t.ends_with('…'), "{}", t);
+        assert!(r.text_width(&t) <= 100.0);
+        assert_eq!(r.truncate("Plage", 100.0), "Plage");
+    }
+
+    #[test]
+    fn header_markers() {
+        let r = renderer();
+        let draw_failed = |stale, timetable_only, failed: &[&str]| {
+            let mut frame = Frame::new(DISPLAY_WIDTH, DISPLAY_HEIGHT);
+            let header = Header {
+                station: String::from("Genève, g. Cornavin"),
+                updated: String::from("12:05"),
+                stale,
+                timetable_only,
+                failed: failed.iter().map(|f| f.to_string()).collect(),
+            };
+            r.draw_header(&mut frame, 40, &header);
+            frame
+        };
+        let draw = |stale, timetable_only| draw_failed(stale, timetable_only, &[]);
+        // the separator of a row ending at 40
+        let separator = 40 - 65 + 47;
+        let black = |f: &Frame, left: i32| {
+            (0..separator)
+                .flat_map(|y| (left..DISPLAY_WIDTH as i32).map(move |x| (x, y)))
+                .filter(|&(x, y)| f.get(x, y))
+                .count()
+        };
+        let fresh = draw(false, false);
+        assert!((0..400).all(|x| fresh.get(x, separator)));
+        assert!((separator + 3..300).all(|y| (0..400).all(|x| !fresh.get(x, y))));
+        // the time is drawn inverted when stale
+        let stale = draw(true, false);
+        assert!(black(&stale, 350) > 50 * separator as usize / 2);
+        assert!(black(&fresh, 350) < 50 * separator as usize / 2);
+        // and prefixed by a marker without realtime data, the station
+        // staying where it is
+        let timetable_only = draw(false, true);
+        assert!(black(&timetable_only, 300) > black(&fresh, 300));
+        assert!(
+            (0..separator).all(|y| (0..200).all(|x| fresh.get(x, y) == timetable_only.get(x, y)))
+        );
+        // the failed stations of a merged board are marked before the time,
+        // which stays where it is, shortening the station name
+        let failed = draw_failed(false, false, &["8503000"]);
+        let time_left =
+            DISPLAY_WIDTH as i32 - 19 / 2 - r.scaled_text_width(r.header_scale, "12:05") as i32;
+        assert_eq!(black(&failed, time_left), black(&fresh, time_left));
+        let before_time =
+            |f: &Frame| (0..separator).any(|y| (240..time_left - 19).any(|x| f.get(x, y)));
+        assert!(before_time(&failed) && !before_time(&fresh));
+        let station_end = |f: &Frame| (0..separator).any(|y| (140..190).any(|x| f.get(x, y)));
+        assert!(station_end(&fresh) && !station_end(&failed));
+    }
+
+    #[test]
+    fn frame_pixels() {
+        let mut f = Frame::new(4, 3);
+        f.fill_rect((1, 1), (5, 1), true);
+        assert!(!f.get(0, 1));
+        assert!(f.get(1, 1) && f.get(3, 1));
+        assert!(!f.get(4, 1) && !f.get(-1, 0));
+        assert_eq!(f.diff_count(&Frame::new(4, 3)), 3);
+        assert_eq!(Frame::from_image(&f.to_image()), f);
+    }
+
+    #[test]
+    fn changed_regions() {
+        let r = renderer();
+        let lines = display_controller_lines();
+        let frame = r.render(&lines, DISPLAY_WIDTH, DISPLAY_HEIGHT);
+        assert_eq!(frame.changed_regions(&frame), vec![]);
+
+        let mut delayed = lines.clone();
+        delayed[2].delay += 3;
+        let regions = r
+            .render(&delayed, DISPLAY_WIDTH, DISPLAY_HEIGHT)
+            .changed_regions(&frame);
+        assert_eq!(regions.len(), 1);
+        let style = Style::default();
+        let delay_x = DISPLAY_WIDTH - style.delay_width;
+        assert!(regions[0].x0 >= delay_x, "{:?}", regions);
+        assert!(regions[0].y0 >= 2 * style.row_height, "{:?}", regions);
+        assert!(regions[0].y1 <= 3 * style.row_height, "{:?}", regions);
+
+        let mut f = Frame::new(4, 3);
+        assert_eq!(
+            f.changed_regions(&Frame::new(3, 4)),
+            vec![Region {
+                x0: 0,
+                y0: 0,
+                x1: 4,
+                y1: 3
+            }]
+        );
+        f.set(1, 0, true);
+        f.set(2, 2, true);
+        assert_eq!(
+            f.changed_regions(&Frame::new(4, 3)),
+            vec![Region {
+                x0: 1,
+                y0: 0,
+                x1: 3,
+                y1: 3
+            }]
+        );
+    }
+
+    #[test]
+    fn frame_rotation() {
+        let mut f = Frame::new(4, 3);
+        f.set(1, 0, true);
+        let r = f.rotate(1);
+        assert_eq!((r.width, r.height), (3, 4));
+        assert!(r.get(2, 1));
+        assert!(f.rotate(2).get(2, 2));
+        assert!(f.rotate(3).get(0, 2));
+        assert_eq!(r.rotate(3), f);
+        assert_eq!(f.rotate(4), f);
+    }
+
+    #[test]
+    fn render_as_display_controller() {
+        let frame = renderer().render(&display_controller_lines(), DISPLAY_WIDTH, DISPLAY_HEIGHT);
+        let expected =
+            Frame::open(&manifest_path("../display_controller/test_display.bmp")).unwrap();
+        // rasterizers differ on the glyph edges only, about 1% of the pixels
+        let diff = frame.diff_count(&expected);
+        assert!(
+            diff < (DISPLAY_WIDTH * DISPLAY_HEIGHT / 50) as usize,
+            "{} pixels differ",
+            diff
+        );
+        // the separators are at the same place
+        for i in 0..4 {
+            let y = 47 + i * 65;
+            assert!((20..=380).all(|x| frame.get(x, y) && expected.get(x, y)));
+        }
+    }
+
+    #[test]
+    fn save_open() {
+        let frame = renderer().render(&display_controller_lines(), DISPLAY_WIDTH, DISPLAY_HEIGHT);
+        for ext in ["bmp", "png"] {
+            let path = std::env::temp_dir().join(format!("frame_{}.{}", std::process::id(), ext));
+            frame.save(&path).unwrap();
+            assert_eq!(Frame::open(&path), Ok(frame.clone()));
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Selection of the departures given a slot on the board.
+
+use crate::model::{Journey, StationBoardElement};
+use std::str::FromStr;
+
+/// A line, optionally restricted to one direction, that should always get a
+/// slot on the board. Parsed from `<number>` or `<number>:<direction>`, the
+/// direction matching any destination containing it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LineSelector {
+    pub number: String,
+    pub direction: Option<String>,
+}
+
+impl LineSelector {
+    /// Whether the journey is of this line, as displayed, and direction.
+    pub fn matches(&self, journey: &Journey) -> bool {
+        journey.line_number() == Some(self.number.as_str())
+            && match &self.direction {
+                Some(d) => journey
+                    .to
+                    .as_ref()
+                    .is_some_and(|to| to.contains(d.as_str())),
+                None => true,
+            }
+    }
+}
+
+impl FromStr for LineSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (number, direction) = match s.split_once(':') {
+            Some((n, d)) => (n.trim(), Some(d.trim())),
+            None => (s.trim(), None),
+        };
+        if number.is_empty() {
+            return Err(format!("missing line number in \"{}\"", s));
+        }
+        if direction == Some("") {
+            return Err(format!("empty direction in \"{}\"", s));
+        }
+        Ok(LineSelector {
+            number: number.to_owned(),
+            direction: direction.map(str::to_owned),
+        })
+    }
+}
+
+/// Parses a `;` separated list of lines, as directions may contain commas
+/// (e.g. `3;6:Vernier, village`).
+pub fn parse_line_selectors(s: &str) -> Result<Vec<LineSelector>, String> {
+    s.split(';')
+        .filter(|l| !l.trim().is_empty())
+        .map(LineSelector::from_str)
+        .collect()
+}
+
+/// Picks at most `max` elements of the board, first giving each selected line
+/// its next departure, then filling the remaining slots by departure time.
+/// The board order is kept, so the result stays sorted by time.
+pub fn select_fair_share<'a>(
+    board: &'a [StationBoardElement],
+    lines: &[LineSelector],
+    max: usize,
+) -> Vec<&'a StationBoardElement> {
+    let mut picked = vec![false; board.len()];
+    let mut nb_picked = 0;
+    for l in lines {
+        if nb_picked == max {
+            break;
+        }
+        if let Some(i) = (0..board.len()).find(|&i| !picked[i] && l.matches(&board[i].journey)) {
+            picked[i] = true;
+            nb_picked += 1;
+        }
+    }
+    for p in picked.iter_mut() {
+        if nb_picked == max {
+            break;
+        }
+        if !*p {
+            *p = true;
+            nb_picked += 1;
+        }
+    }
+    board
+        .iter()
+        .zip(picked)
+        .filter(|(_, p)| *p)
+        .map(|(e, _)| e)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::stationboard_geneva;
+
+    fn selected_lines(selected: &[&StationBoardElement]) -> Vec<(String, String)> {
+        selected
+            .iter()
+            .map(|e| {
+                (
+                    e.journey.number.clone().unwrap(),
+                    e.journey.to.clone().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_lines() {
+        assert_eq!(
+            parse_line_selectors("3; 6:Vernier, village;").unwrap(),
+            vec![
+                LineSelector {
+                    number: String::from("3"),
+                    direction: None,
+                },
+                LineSelector {
+                    number: String::from("6"),
+                    direction: Some(String::from("Vernier, village")),
+                },
+            ]
+        );
+        assert_eq!(parse_line_selectors("").unwrap(), vec![]);
+        assert!(parse_line_selectors("3;:Onex").is_err());
+        assert!(parse_line_selectors("3:").is_err());
+    }
+
+    #[test]
+    fn match_displayed_line_number() {
+        let mut journey = stationboard_geneva().stationboard[0].journey.clone();
+        let ir: LineSelector = "IR".parse().unwrap();
+        assert!(!ir.matches(&journey));
+        journey.number = None;
+        journey.category = Some(String::from("IR"));
+        assert!(ir.matches(&journey));
+        // the number takes precedence over the category
+        journey.number = Some(String::from("15"));
+        assert!(!ir.matches(&journey));
+        assert!("15".parse::<LineSelector>().unwrap().matches(&journey));
+    }
+
+    #[test]
+    fn fair_share_without_lines_keeps_time_order() {
+        let s = stationboard_geneva();
+        let selected = select_fair_share(&s.stationboard, &[], 5);
+        let expected: Vec<&StationBoardElement> = s.stationboard.iter().take(5).collect();
+        assert_eq!(selected, expected);
+    }
+
+    #[test]
+    fn fair_share_guarantees_each_line() {
+        let s = stationboard_geneva();
+        let lines = parse_line_selectors("6:Vernier;18;3").unwrap();
+        let selected = select_fair_share(&s.stationboard, &lines, 5);
+        assert_eq!(
+            selected_lines(&selected),
+            vec![
+                (
+                    String::from("3"),
+                    String::from("Grand-Saconnex, Giacometti")
+                ),
+                (String::from("20"), String::from("Veyrier, Tournettes")),
+                (String::from("5"), String::from("Thônex, Vallard")),
+                (String::from("6"), String::from("Vernier, village")),
+                (String::from("18"), String::from("Meyrin, CERN")),
+            ]
+        );
+    }
+
+    #[test]
+    fn fair_share_lines_exceeding_slots() {
+        let s = stationboard_geneva();
+        let lines = parse_line_selectors("1;6;10;18;14;15").unwrap();
+        let selected = select_fair_share(&s.stationboard, &lines, 3);
+        assert_eq!(
+            selected_lines(&selected),
+            vec![
+                (String::from("6"), String::from("Genève, Plage")),
+                (String::from("10"), String::from("Genève, Rive")),
+                (String::from("1"), String::from("Genève, Jardin Botanique")),
+            ]
+        );
+    }
+}
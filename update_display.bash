@@ -6,6 +6,10 @@ cd -- "$( dirname -- "${BASH_SOURCE[0]}" )"
 # must go to this directory to get the .env file
 cd api_fetcher
 ./target/release/api_fetcher
+# 3 when the board did not change, the display being left as it is
+if [ $? -eq 3 ]; then
+    exit 0
+fi
 
 cd ..
 source .venv/bin/activate